@@ -0,0 +1,172 @@
+//! Byte offsets of account fields used to narrow `getProgramAccounts` queries.
+//!
+//! Every Jito account starts with an 8-byte header holding the discriminator, followed by the
+//! `#[repr(C)]` account struct.
+
+use std::mem::size_of;
+
+use jito_vault_core::{
+    delegation_state::DelegationState, vault_operator_delegation::VaultOperatorDelegation,
+};
+use solana_client::rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType};
+use solana_sdk::pubkey::Pubkey;
+
+/// Length of the header in front of every account struct
+pub const HEADER_LEN: usize = 8;
+
+const PUBKEY_LEN: usize = 32;
+
+/// `VaultUpdateStateTracker::vault`
+pub const TRACKER_VAULT_OFFSET: usize = HEADER_LEN;
+
+/// `VaultUpdateStateTracker::ncn_epoch`
+pub const TRACKER_NCN_EPOCH_OFFSET: usize = TRACKER_VAULT_OFFSET + PUBKEY_LEN;
+
+/// `VaultOperatorDelegation::vault`
+pub const DELEGATION_VAULT_OFFSET: usize = HEADER_LEN;
+
+/// `VaultOperatorDelegation::operator`
+pub const DELEGATION_OPERATOR_OFFSET: usize = DELEGATION_VAULT_OFFSET + PUBKEY_LEN;
+
+/// `VaultOperatorDelegation::index`, after `delegation_state` and `last_update_slot`
+pub const DELEGATION_INDEX_OFFSET: usize =
+    DELEGATION_OPERATOR_OFFSET + PUBKEY_LEN + size_of::<DelegationState>() + 8;
+
+/// Bytes of a `VaultOperatorDelegation` account the cranker reads, from the header up to and
+/// including `index`. The `bump` and the reserved bytes after it are left out.
+pub const DELEGATION_READ_LEN: usize = DELEGATION_INDEX_OFFSET + 8;
+
+/// Pads a `VaultOperatorDelegation` account read up to [`DELEGATION_READ_LEN`] back to its full
+/// length with zeros, so it can be deserialized. The `bump` of the result is always 0.
+pub fn pad_delegation(mut data: Vec<u8>) -> Vec<u8> {
+    data.resize(HEADER_LEN + size_of::<VaultOperatorDelegation>(), 0);
+    data
+}
+
+/// `NcnVaultTicket::ncn`
+pub const NCN_VAULT_TICKET_NCN_OFFSET: usize = HEADER_LEN;
+
+/// `NcnVaultTicket::vault`
+pub const NCN_VAULT_TICKET_VAULT_OFFSET: usize = NCN_VAULT_TICKET_NCN_OFFSET + PUBKEY_LEN;
+
+/// `NcnOperatorState::ncn`
+pub const NCN_OPERATOR_STATE_NCN_OFFSET: usize = HEADER_LEN;
+
+/// `NcnOperatorState::operator`
+pub const NCN_OPERATOR_STATE_OPERATOR_OFFSET: usize = NCN_OPERATOR_STATE_NCN_OFFSET + PUBKEY_LEN;
+
+/// Filter on the account discriminator.
+pub fn discriminator_filter(discriminator: u8) -> RpcFilterType {
+    RpcFilterType::Memcmp(Memcmp::new(
+        0,
+        MemcmpEncodedBytes::Bytes(vec![discriminator]),
+    ))
+}
+
+/// Filter on a `Pubkey` field.
+pub fn pubkey_filter(offset: usize, pubkey: &Pubkey) -> RpcFilterType {
+    RpcFilterType::Memcmp(Memcmp::new(
+        offset,
+        MemcmpEncodedBytes::Bytes(pubkey.to_bytes().to_vec()),
+    ))
+}
+
+/// Filter on a `PodU64` field.
+pub fn u64_filter(offset: usize, value: u64) -> RpcFilterType {
+    RpcFilterType::Memcmp(Memcmp::new(
+        offset,
+        MemcmpEncodedBytes::Bytes(value.to_le_bytes().to_vec()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use jito_bytemuck::{AccountDeserialize, Discriminator};
+    use jito_restaking_core::{
+        ncn_operator_state::NcnOperatorState, ncn_vault_ticket::NcnVaultTicket,
+    };
+    use jito_vault_core::vault_update_state_tracker::VaultUpdateStateTracker;
+
+    use super::*;
+
+    fn field(account: &[u8], offset: usize, len: usize) -> &[u8] {
+        &account[offset - HEADER_LEN..offset - HEADER_LEN + len]
+    }
+
+    #[test]
+    fn test_tracker_offsets() {
+        let vault = Pubkey::new_unique();
+        let tracker = VaultUpdateStateTracker::new(vault, 42, 0);
+        let bytes = bytemuck::bytes_of(&tracker);
+
+        assert_eq!(field(bytes, TRACKER_VAULT_OFFSET, 32), vault.as_ref());
+        assert_eq!(
+            field(bytes, TRACKER_NCN_EPOCH_OFFSET, 8),
+            42u64.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn test_delegation_offsets() {
+        let vault = Pubkey::new_unique();
+        let operator = Pubkey::new_unique();
+        let delegation = VaultOperatorDelegation::new(vault, operator, 7, 0, 0);
+        let bytes = bytemuck::bytes_of(&delegation);
+
+        assert_eq!(field(bytes, DELEGATION_VAULT_OFFSET, 32), vault.as_ref());
+        assert_eq!(
+            field(bytes, DELEGATION_OPERATOR_OFFSET, 32),
+            operator.as_ref()
+        );
+        assert_eq!(field(bytes, DELEGATION_INDEX_OFFSET, 8), 7u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_pad_delegation() {
+        let vault = Pubkey::new_unique();
+        let operator = Pubkey::new_unique();
+        let delegation = VaultOperatorDelegation::new(vault, operator, 7, 255, 0);
+        let mut account = vec![0; HEADER_LEN];
+        account[0] = VaultOperatorDelegation::DISCRIMINATOR;
+        account.extend_from_slice(bytemuck::bytes_of(&delegation));
+        account.truncate(DELEGATION_READ_LEN);
+
+        let data = pad_delegation(account);
+        let read = VaultOperatorDelegation::try_from_slice_unchecked(&data).unwrap();
+        assert_eq!(read.vault, vault);
+        assert_eq!(read.operator, operator);
+        assert_eq!(read.index(), 7);
+        assert_eq!(read.bump, 0);
+    }
+
+    #[test]
+    fn test_ncn_vault_ticket_offsets() {
+        let ncn = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let ticket = NcnVaultTicket::new(ncn, vault, 0, 0, 0);
+        let bytes = bytemuck::bytes_of(&ticket);
+
+        assert_eq!(field(bytes, NCN_VAULT_TICKET_NCN_OFFSET, 32), ncn.as_ref());
+        assert_eq!(
+            field(bytes, NCN_VAULT_TICKET_VAULT_OFFSET, 32),
+            vault.as_ref()
+        );
+    }
+
+    #[test]
+    fn test_ncn_operator_state_offsets() {
+        let ncn = Pubkey::new_unique();
+        let operator = Pubkey::new_unique();
+        let state = NcnOperatorState::new(ncn, operator, 0, 0, 0);
+        let bytes = bytemuck::bytes_of(&state);
+
+        assert_eq!(
+            field(bytes, NCN_OPERATOR_STATE_NCN_OFFSET, 32),
+            ncn.as_ref()
+        );
+        assert_eq!(
+            field(bytes, NCN_OPERATOR_STATE_OPERATOR_OFFSET, 32),
+            operator.as_ref()
+        );
+    }
+}
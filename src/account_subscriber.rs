@@ -0,0 +1,258 @@
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use futures::StreamExt;
+use jito_bytemuck::{AccountDeserialize, Discriminator};
+use jito_vault_core::{
+    vault::Vault, vault_operator_delegation::VaultOperatorDelegation,
+    vault_update_state_tracker::VaultUpdateStateTracker,
+};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::pubsub_client::PubsubClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+};
+use solana_sdk::{account::Account, commitment_config::CommitmentConfig, pubkey::Pubkey};
+use tokio::sync::{mpsc::UnboundedSender, RwLock};
+
+use crate::account_layout::discriminator_filter;
+
+/// Derives the WebSocket URL from an RPC URL, following the Solana CLI convention of using the
+/// next port when one is given explicitly.
+pub fn websocket_url(rpc_url: &str) -> String {
+    let ws_url = if let Some(rest) = rpc_url.strip_prefix("https://") {
+        format!("wss://{rest}")
+    } else if let Some(rest) = rpc_url.strip_prefix("http://") {
+        format!("ws://{rest}")
+    } else {
+        rpc_url.to_string()
+    };
+
+    // ws://host:8899/path -> ws://host:8900/path
+    let (scheme, rest) = ws_url.split_once("://").unwrap_or(("", &ws_url));
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    if let Some((host, port)) = authority.rsplit_once(':') {
+        if let Ok(port) = port.parse::<u16>() {
+            return format!("{scheme}://{host}:{}{path}", port.saturating_add(1));
+        }
+    }
+
+    ws_url
+}
+
+/// In-memory view of the Vault, VaultOperatorDelegation and VaultUpdateStateTracker accounts.
+#[derive(Debug, Default)]
+pub struct AccountCache {
+    vaults: HashMap<Pubkey, Vault>,
+    delegations: HashMap<Pubkey, VaultOperatorDelegation>,
+
+    /// Keyed by tracker address
+    trackers: HashMap<Pubkey, VaultUpdateStateTracker>,
+}
+
+impl AccountCache {
+    /// Replaces the whole view with the result of a full rescan.
+    pub fn replace(
+        &mut self,
+        vaults: &HashMap<Pubkey, Vault>,
+        delegations: &[(Pubkey, VaultOperatorDelegation)],
+        trackers: &HashMap<Pubkey, (Pubkey, VaultUpdateStateTracker)>,
+    ) {
+        self.vaults = vaults.clone();
+        self.delegations = delegations.iter().copied().collect();
+        self.trackers = trackers.values().copied().collect();
+    }
+
+    /// Applies an account update and returns the vault the account belongs to.
+    ///
+    /// Accounts of other types, or that fail to deserialize, are ignored.
+    pub fn update(&mut self, pubkey: Pubkey, data: &[u8]) -> Option<Pubkey> {
+        match data.first() {
+            Some(&d) if d == Vault::DISCRIMINATOR => {
+                let vault = *Vault::try_from_slice_unchecked(data).ok()?;
+                self.vaults.insert(pubkey, vault);
+                Some(pubkey)
+            }
+            Some(&d) if d == VaultOperatorDelegation::DISCRIMINATOR => {
+                let delegation = *VaultOperatorDelegation::try_from_slice_unchecked(data).ok()?;
+                self.delegations.insert(pubkey, delegation);
+                Some(delegation.vault)
+            }
+            Some(&d) if d == VaultUpdateStateTracker::DISCRIMINATOR => {
+                let tracker = *VaultUpdateStateTracker::try_from_slice_unchecked(data).ok()?;
+                self.trackers.insert(pubkey, tracker);
+                Some(tracker.vault)
+            }
+            _ => None,
+        }
+    }
+
+    /// Removes a tracker, which closing does not report through `programSubscribe`.
+    pub fn remove_tracker(&mut self, tracker: &Pubkey) {
+        self.trackers.remove(tracker);
+    }
+
+    pub fn vault(&self, vault: &Pubkey) -> Option<Vault> {
+        self.vaults.get(vault).copied()
+    }
+
+    pub fn delegations(&self, vault: &Pubkey) -> Vec<(Pubkey, VaultOperatorDelegation)> {
+        self.delegations
+            .iter()
+            .filter(|(_pubkey, delegation)| delegation.vault == *vault)
+            .map(|(pubkey, delegation)| (*pubkey, *delegation))
+            .collect()
+    }
+
+    /// Returns the tracker of `vault` with the latest NCN epoch.
+    pub fn tracker(&self, vault: &Pubkey) -> Option<(Pubkey, VaultUpdateStateTracker)> {
+        self.trackers
+            .iter()
+            .filter(|(_pubkey, tracker)| tracker.vault == *vault)
+            .max_by_key(|(_pubkey, tracker)| tracker.ncn_epoch())
+            .map(|(pubkey, tracker)| (*pubkey, *tracker))
+    }
+}
+
+/// Keeps an [`AccountCache`] up to date through `programSubscribe` and reports which vault
+/// changed.
+pub struct AccountSubscriber {
+    ws_url: String,
+    commitment: CommitmentConfig,
+    vault_program_id: Pubkey,
+}
+
+impl AccountSubscriber {
+    pub fn new(ws_url: &str, commitment: CommitmentConfig, vault_program_id: Pubkey) -> Self {
+        Self {
+            ws_url: ws_url.to_string(),
+            commitment,
+            vault_program_id,
+        }
+    }
+
+    /// Subscribes until the connection drops, reconnecting after a delay.
+    ///
+    /// Returns once `sender` is closed.
+    pub async fn subscribe(
+        &self,
+        cache: Arc<RwLock<AccountCache>>,
+        sender: UnboundedSender<Pubkey>,
+    ) {
+        while !sender.is_closed() {
+            if let Err(e) = self.run(&cache, &sender).await {
+                log::error!("Account subscription failed: {e:?}");
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    }
+
+    async fn run(
+        &self,
+        cache: &RwLock<AccountCache>,
+        sender: &UnboundedSender<Pubkey>,
+    ) -> anyhow::Result<()> {
+        let pubsub_client = PubsubClient::new(&self.ws_url)
+            .await
+            .with_context(|| format!("Failed to connect to {}", self.ws_url))?;
+
+        let mut streams = Vec::new();
+        let mut unsubscribes = Vec::new();
+        for discriminator in [
+            Vault::DISCRIMINATOR,
+            VaultOperatorDelegation::DISCRIMINATOR,
+            VaultUpdateStateTracker::DISCRIMINATOR,
+        ] {
+            let (stream, unsubscribe) = pubsub_client
+                .program_subscribe(
+                    &self.vault_program_id,
+                    Some(RpcProgramAccountsConfig {
+                        filters: Some(vec![discriminator_filter(discriminator)]),
+                        account_config: RpcAccountInfoConfig {
+                            encoding: Some(UiAccountEncoding::Base64),
+                            commitment: Some(self.commitment),
+                            ..RpcAccountInfoConfig::default()
+                        },
+                        ..RpcProgramAccountsConfig::default()
+                    }),
+                )
+                .await
+                .context("Failed to subscribe to vault program accounts")?;
+            streams.push(stream);
+            unsubscribes.push(unsubscribe);
+        }
+        log::info!("Subscribed to vault program accounts: {}", self.ws_url);
+
+        let mut updates = futures::stream::select_all(streams);
+        while let Some(response) = updates.next().await {
+            let Ok(pubkey) = Pubkey::from_str(&response.value.pubkey) else {
+                continue;
+            };
+            let Some(account) = response.value.account.decode::<Account>() else {
+                continue;
+            };
+
+            let vault = cache.write().await.update(pubkey, &account.data);
+            if let Some(vault) = vault {
+                log::debug!("Account updated: {pubkey}, vault: {vault}");
+                if sender.send(vault).is_err() {
+                    break;
+                }
+            }
+        }
+
+        drop(updates);
+        for unsubscribe in unsubscribes {
+            unsubscribe().await;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_websocket_url() {
+        assert_eq!(
+            websocket_url("https://api.devnet.solana.com"),
+            "wss://api.devnet.solana.com"
+        );
+        assert_eq!(
+            websocket_url("http://127.0.0.1:8899"),
+            "ws://127.0.0.1:8900"
+        );
+        assert_eq!(
+            websocket_url("http://localhost:8899/token"),
+            "ws://localhost:8900/token"
+        );
+    }
+
+    #[test]
+    fn test_tracker_latest_epoch() {
+        let vault = Pubkey::new_unique();
+        let old = (
+            Pubkey::new_unique(),
+            VaultUpdateStateTracker::new(vault, 1, 0),
+        );
+        let new = (
+            Pubkey::new_unique(),
+            VaultUpdateStateTracker::new(vault, 2, 0),
+        );
+        let other = (
+            Pubkey::new_unique(),
+            VaultUpdateStateTracker::new(Pubkey::new_unique(), 3, 0),
+        );
+
+        let mut cache = AccountCache::default();
+        cache.trackers = HashMap::from([old, new, other]);
+
+        assert_eq!(cache.tracker(&vault).unwrap().0, new.0);
+        assert!(cache.tracker(&Pubkey::new_unique()).is_none());
+
+        cache.remove_tracker(&new.0);
+        assert_eq!(cache.tracker(&vault).unwrap().0, old.0);
+    }
+}
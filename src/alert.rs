@@ -0,0 +1,319 @@
+//! Alerts on missed vault updates, a low payer balance and a failing run loop.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    time::Duration,
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use serde_json::json;
+use solana_sdk::{native_token::lamports_to_sol, pubkey::Pubkey};
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Warning => "warning",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Alert {
+    /// The NCN epoch ended without the vault being fully updated
+    VaultMissedEpoch { vault: Pubkey, ncn_epoch: u64 },
+
+    /// The payer balance is low, initializations are paused when it is below the floor
+    PayerLow {
+        payer: Pubkey,
+        balance: u64,
+        estimated_cost: u64,
+        below_floor: bool,
+    },
+
+    /// Consecutive cycles of the run loop failed
+    LoopFailing {
+        consecutive_failures: u32,
+        error: String,
+    },
+}
+
+impl Alert {
+    /// Identifies the condition the alert reports, alerts with the same key are deduplicated.
+    pub fn key(&self) -> String {
+        match self {
+            Self::VaultMissedEpoch { vault, ncn_epoch } => {
+                format!("vault_missed_epoch:{vault}:{ncn_epoch}")
+            }
+            Self::PayerLow {
+                payer,
+                below_floor: false,
+                ..
+            } => format!("payer_low:{payer}"),
+            Self::PayerLow {
+                payer,
+                below_floor: true,
+                ..
+            } => format!("payer_low:{payer}:below_floor"),
+            Self::LoopFailing { .. } => "loop_failing".to_string(),
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::PayerLow {
+                below_floor: false, ..
+            } => Severity::Warning,
+            _ => Severity::Critical,
+        }
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::VaultMissedEpoch { vault, ncn_epoch } => {
+                write!(f, "Vault {vault} was not updated in NCN epoch {ncn_epoch}")
+            }
+            Self::PayerLow {
+                payer,
+                balance,
+                estimated_cost,
+                below_floor,
+            } => {
+                write!(
+                    f,
+                    "Payer {payer} balance is {} SOL, the next cycle costs about {} SOL",
+                    lamports_to_sol(*balance),
+                    lamports_to_sol(*estimated_cost)
+                )?;
+                if *below_floor {
+                    write!(f, ", initializations are paused")?;
+                }
+                Ok(())
+            }
+            Self::LoopFailing {
+                consecutive_failures,
+                error,
+            } => write!(
+                f,
+                "Run loop failed {consecutive_failures} times in a row: {error}"
+            ),
+        }
+    }
+}
+
+/// Delivers alerts to an external service.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, alert: &Alert) -> anyhow::Result<()>;
+}
+
+/// Posts every alert as JSON to a URL.
+///
+/// The payload has a `text` field, so Slack and Discord compatible incoming webhooks can display
+/// it as is.
+pub struct WebhookNotifier {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            url: url.to_string(),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .context("Failed to build the webhook client")?,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, alert: &Alert) -> anyhow::Result<()> {
+        let payload = json!({
+            "key": alert.key(),
+            "severity": alert.severity().as_str(),
+            "text": alert.to_string(),
+        });
+
+        self.client
+            .post(&self.url)
+            .json(&payload)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+/// Sends alerts through the notifiers, deduplicated and rate limited.
+pub struct Alerter {
+    notifiers: Vec<Box<dyn Notifier>>,
+
+    /// An alert is not sent again until this long after it was last sent
+    dedup_window: Duration,
+
+    /// At most `rate_limit` alerts are sent per `rate_window`
+    rate_limit: usize,
+    rate_window: Duration,
+
+    /// When each alert key was last sent
+    sent: HashMap<String, Instant>,
+
+    /// When the alerts in the current rate window were sent
+    recent: VecDeque<Instant>,
+}
+
+impl Alerter {
+    pub fn new(
+        notifiers: Vec<Box<dyn Notifier>>,
+        dedup_window: Duration,
+        rate_limit: usize,
+        rate_window: Duration,
+    ) -> Self {
+        Self {
+            notifiers,
+            dedup_window,
+            rate_limit,
+            rate_window,
+            sent: HashMap::new(),
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Logs `alert` and sends it, unless it was sent recently or the rate limit is reached.
+    ///
+    /// Returns whether the alert was sent. Failing notifiers are logged and do not fail the
+    /// caller.
+    pub async fn fire(&mut self, alert: Alert) -> bool {
+        match alert.severity() {
+            Severity::Warning => log::warn!("Alert: {alert}"),
+            Severity::Critical => log::error!("Alert: {alert}"),
+        }
+
+        let now = Instant::now();
+        let key = alert.key();
+        if let Some(sent_at) = self.sent.get(&key) {
+            if now.duration_since(*sent_at) < self.dedup_window {
+                return false;
+            }
+        }
+
+        while let Some(sent_at) = self.recent.front() {
+            if now.duration_since(*sent_at) < self.rate_window {
+                break;
+            }
+            self.recent.pop_front();
+        }
+        if self.recent.len() >= self.rate_limit {
+            log::warn!("Alert rate limit reached, dropping: {key}");
+            return false;
+        }
+
+        for notifier in self.notifiers.iter() {
+            if let Err(e) = notifier.notify(&alert).await {
+                log::error!("Failed to send alert {key}: {e:?}");
+            }
+        }
+        self.sent.insert(key, now);
+        self.recent.push_back(now);
+
+        true
+    }
+
+    /// Forgets the alerts whose key starts with `prefix`, so they are sent right away if the
+    /// condition comes back.
+    pub fn resolve(&mut self, prefix: &str) {
+        self.sent.retain(|key, _sent_at| !key.starts_with(prefix));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc, Arc, Mutex};
+
+    use super::*;
+    use crate::test_server;
+
+    struct RecordingNotifier(Arc<Mutex<Vec<String>>>);
+
+    #[async_trait]
+    impl Notifier for RecordingNotifier {
+        async fn notify(&self, alert: &Alert) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push(alert.key());
+            Ok(())
+        }
+    }
+
+    fn loop_failing() -> Alert {
+        Alert::LoopFailing {
+            consecutive_failures: 3,
+            error: "timeout".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dedup_and_rate_limit() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let mut alerter = Alerter::new(
+            vec![Box::new(RecordingNotifier(sent.clone()))],
+            Duration::from_secs(3600),
+            2,
+            Duration::from_secs(3600),
+        );
+        let vault = Pubkey::new_unique();
+
+        assert!(alerter.fire(loop_failing()).await);
+        assert!(!alerter.fire(loop_failing()).await);
+
+        alerter.resolve("loop_failing");
+        assert!(alerter.fire(loop_failing()).await);
+
+        // Two alerts sent in the window already
+        assert!(
+            !alerter
+                .fire(Alert::VaultMissedEpoch {
+                    vault,
+                    ncn_epoch: 1
+                })
+                .await
+        );
+        assert_eq!(sent.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_notifier() {
+        // Passes on the body of every request
+        let (bodies, received) = mpsc::channel();
+        let url = test_server::serve(move |_head, body| {
+            bodies.send(body.to_string()).unwrap();
+            String::new()
+        })
+        .await;
+        let url = format!("{url}/alerts");
+
+        WebhookNotifier::new(&url)
+            .unwrap()
+            .notify(&loop_failing())
+            .await
+            .unwrap();
+
+        let body: serde_json::Value = serde_json::from_str(&received.try_recv().unwrap()).unwrap();
+        assert_eq!(body["key"], "loop_failing");
+        assert_eq!(body["severity"], "critical");
+        assert_eq!(body["text"], "Run loop failed 3 times in a row: timeout");
+    }
+}
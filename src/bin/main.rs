@@ -1,11 +1,47 @@
-use std::{cmp::Ordering, collections::HashMap, fs::File, path::PathBuf, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
+use anyhow::Context;
 use chrono_crank::{
-    vault_program_handler::VaultProgramHandler, vault_state_manager::VaultStateManager,
+    account_subscriber::{websocket_url, AccountCache, AccountSubscriber},
+    alert::{Alert, Alerter, Notifier, WebhookNotifier},
+    bootstrap::{self, Bootstrap, Topology},
+    compute_budget::{ComputeUnitEstimator, DEFAULT_COMPUTE_UNIT_MARGIN_PERCENT},
+    history::{ActionStatus, CrankHistory, HistoryFilter, VaultCost},
+    nonce_pool::NoncePool,
+    payer_monitor::{estimate_cost, PayerStatus, PayerThresholds},
+    payer_pool::{PayerPool, VaultPayer},
+    priority_fee::{PriorityFeeConfig, PriorityFeeEscalator},
+    report::{EpochReport, ReportFormat},
+    restaking_handler::RestakingHandler,
+    signer::SignerSource,
+    spending::{self, SpendingBudget},
+    transaction_export::{self, ExportFormat, TransactionExporter},
+    vault_admin_handler::VaultAdminHandler,
+    vault_program_handler::{ProgramSnapshot, VaultProgramHandler},
+    vault_state_manager::{VaultAction, VaultStateManager},
 };
 use clap::{Parser, Subcommand};
-use jito_vault_core::{vault::Vault, vault_operator_delegation::VaultOperatorDelegation};
-use solana_sdk::{pubkey::Pubkey, signature::read_keypair_file};
+use jito_vault_core::vault_operator_delegation::VaultOperatorDelegation;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    native_token::{lamports_to_sol, sol_to_lamports},
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signature},
+};
+use tokio::{
+    sync::{mpsc, RwLock},
+    time::Instant,
+};
+
+/// Delay before the next cycle after a failed one, if shorter than the rescan interval
+const FAILED_CYCLE_RETRY_DELAY: Duration = Duration::from_secs(30);
 
 #[derive(Parser)]
 struct Args {
@@ -13,9 +49,80 @@ struct Args {
     #[arg(short, long, env, default_value = "https://api.devnet.solana.com")]
     rpc_url: String,
 
-    /// Path to keypair used to pay
-    #[arg(long, env, default_value = "~/.config/solana/id.json")]
-    keypair: PathBuf,
+    /// WebSocket URL for the cluster, derived from the RPC URL if omitted
+    #[arg(long, env)]
+    ws_url: Option<String>,
+
+    /// Commitment level of RPC requests and subscriptions (processed, confirmed or finalized)
+    #[arg(long, env, default_value = "confirmed")]
+    commitment: CommitmentConfig,
+
+    /// Payer signers, repeated or comma separated: a keypair file (`~` and $VARS are expanded),
+    /// env:NAME for a base58 or JSON array keypair in an environment variable, stdin, config or
+    /// config:PATH for the keypair of a Solana CLI config, or the URL of a remote signer. The
+    /// first one pays admin operations. Defaults to the keypair of the Solana CLI config
+    #[arg(long = "keypair", env = "KEYPAIR", value_delimiter = ',')]
+    keypairs: Vec<SignerSource>,
+
+    /// Pays the transactions of a vault with one of the keypairs, as VAULT=PAYER. Other vaults
+    /// are spread over the payers by their address, each always going through the same one
+    #[arg(long = "vault-payer", value_name = "VAULT=PAYER")]
+    vault_payers: Vec<VaultPayer>,
+
+    /// Write admin and crank transactions out instead of sending them, for offline or multisig
+    /// signing: base58 or base64 for unsigned serialized transactions, json for the instruction
+    /// list. The payer does not sign, so it can be given as pubkey:ADDRESS. Exported transactions
+    /// use durable nonces and need --nonce-accounts
+    #[arg(long, value_name = "FORMAT")]
+    export: Option<ExportFormat>,
+
+    /// File to append exported transactions to instead of stdout
+    #[arg(long, requires = "export")]
+    export_out: Option<PathBuf>,
+
+    /// Durable nonce accounts per payer for initialize, crank and close transactions and exported
+    /// transactions, created on startup. With 0, transactions use a recent blockhash
+    #[arg(long, env, default_value_t = 0)]
+    nonce_accounts: usize,
+
+    /// Cranks sent per v0 transaction, through an address lookup table per vault that is created
+    /// and extended as needed. With 1, cranks are sent one per legacy transaction
+    #[arg(long, env, default_value_t = 1)]
+    crank_batch_size: usize,
+
+    /// Margin added to the compute units consumed in the simulation of a transaction to set its
+    /// compute-unit limit, in percent
+    #[arg(long, env, default_value_t = DEFAULT_COMPUTE_UNIT_MARGIN_PERCENT)]
+    compute_unit_margin_percent: u64,
+
+    /// Priority fee of initialize, crank and close transactions at the NCN epoch boundary, in
+    /// micro-lamports per compute unit
+    #[arg(long, env, default_value_t = 0)]
+    priority_fee_base: u64,
+
+    /// Ceiling of the priority fee, in micro-lamports per compute unit. The fee grows from the
+    /// base to the ceiling through the NCN epoch. With 0, no priority fee is paid
+    #[arg(long, env, default_value_t = 0)]
+    priority_fee_max: u64,
+
+    /// Share of the range from the base to the ceiling added to the priority fee of a vault per
+    /// consecutive failed send, in percent
+    #[arg(long, env, default_value_t = 25)]
+    priority_fee_failure_step_percent: u64,
+
+    /// SOL that `run` may spend on all vaults and overhead per NCN epoch, in fees and unreturned
+    /// rent. Trackers are only initialized while it covers another update, initialized ones are
+    /// always cranked and closed
+    #[arg(long, env)]
+    epoch_budget_sol: Option<f64>,
+
+    /// SOL that `run` may spend on each vault per NCN epoch
+    #[arg(long, env)]
+    vault_epoch_budget_sol: Option<f64>,
+
+    /// Path to the SQLite database recording crank history
+    #[arg(long, env, default_value = "history.sqlite")]
+    history_db: PathBuf,
 
     /// Vault program ID (Pubkey as base58 string)
     #[arg(
@@ -39,8 +146,431 @@ struct Args {
 
 #[derive(Subcommand)]
 enum Commands {
-    Run,
+    Run {
+        /// Subscribe to vault program accounts and crank a vault as soon as it changes
+        #[arg(long)]
+        subscribe: bool,
+
+        /// Seconds between full rescans of the vault program
+        #[arg(long, default_value_t = 60 * 60)]
+        rescan_interval_secs: u64,
+
+        /// Warn when the payer balance, in SOL, minus the estimated cost of a cycle is below this
+        #[arg(long, default_value_t = 1.0)]
+        warn_balance_sol: f64,
+
+        /// Pause initializations, which pay the tracker rent, below this payer balance in SOL
+        #[arg(long, default_value_t = 0.1)]
+        min_balance_sol: f64,
+
+        /// URL to post alerts to as JSON
+        #[arg(long, env)]
+        webhook_url: Option<String>,
+
+        /// Seconds before an alert for the same condition is sent again
+        #[arg(long, default_value_t = 60 * 60)]
+        alert_dedup_secs: u64,
+
+        /// Maximum number of alerts sent per hour
+        #[arg(long, default_value_t = 10)]
+        alert_rate_limit: usize,
+
+        /// Consecutive failed cycles before alerting
+        #[arg(long, default_value_t = 3)]
+        alert_after_failures: u32,
+
+        /// File to write spending metrics to in the Prometheus text format after every cycle
+        #[arg(long, env)]
+        metrics_file: Option<PathBuf>,
+    },
     GetVaultUpdateStateTrackers,
+
+    /// Summarize how the vaults were updated in an NCN epoch
+    Report {
+        /// NCN epoch to report on
+        #[arg(long)]
+        epoch: u64,
+
+        /// Output format: json, csv or markdown
+        #[arg(long, default_value = "markdown")]
+        format: ReportFormat,
+
+        /// File to write the report to instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Show the lamports spent per vault in an NCN epoch and what is left of the budgets given, or
+    /// else of the budgets of the last `run`
+    Status {
+        /// NCN epoch to show, the current one if omitted
+        #[arg(long)]
+        epoch: Option<u64>,
+    },
+
+    /// Show the actions recorded by `run`, most recent first
+    History {
+        /// Only show actions of this vault
+        #[arg(long)]
+        vault: Option<Pubkey>,
+
+        /// Only show actions of this NCN epoch
+        #[arg(long)]
+        epoch: Option<u64>,
+
+        /// Only show actions with this status (planned, succeeded, failed or interrupted)
+        #[arg(long)]
+        status: Option<ActionStatus>,
+
+        /// Maximum number of actions to show
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+
+    /// Run initialize, crank and close once for a single vault
+    CrankVault {
+        /// Vault address (Pubkey as base58 string)
+        vault: Pubkey,
+
+        /// NCN epoch of the tracker to close instead of the current one. The program only
+        /// initializes and cranks trackers of the current epoch, so an earlier epoch requires
+        /// --skip-initialize and --skip-crank
+        #[arg(long)]
+        epoch: Option<u64>,
+
+        /// Skip initializing the VaultUpdateStateTracker
+        #[arg(long)]
+        skip_initialize: bool,
+
+        /// Skip cranking the VaultOperatorDelegations
+        #[arg(long)]
+        skip_crank: bool,
+
+        /// Skip closing the VaultUpdateStateTracker
+        #[arg(long)]
+        skip_close: bool,
+    },
+
+    /// Jito restaking program admin operations
+    Restaking {
+        #[command(subcommand)]
+        action: RestakingCommands,
+    },
+
+    /// Jito vault program admin operations
+    Vault {
+        #[command(subcommand)]
+        action: VaultCommands,
+    },
+
+    /// Send transactions signed outside the cranker, one base64 or base58 transaction per line
+    Submit {
+        /// File to read the transactions from instead of stdin
+        file: Option<PathBuf>,
+    },
+
+    /// Seed a local test validator with the accounts described in a topology file
+    Bootstrap {
+        /// Path to the topology JSON file
+        topology: PathBuf,
+
+        /// Directory for base keypairs and the manifest of created addresses
+        #[arg(long, default_value = "bootstrap")]
+        out_dir: PathBuf,
+
+        /// Stop instead of waiting for warmed up tickets to become active
+        #[arg(long)]
+        no_wait: bool,
+
+        /// Allow running against an RPC URL that is not local
+        #[arg(long)]
+        allow_remote: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum RestakingCommands {
+    /// Initialize the restaking config with `--vault-program-id` as its vault program
+    InitConfig,
+
+    /// Initialize an NCN
+    InitNcn {
+        /// Path to the base keypair, a new one is generated if omitted
+        #[arg(long)]
+        base_keypair: Option<PathBuf>,
+
+        /// Path to write the generated base keypair to
+        #[arg(long)]
+        base_keypair_out: Option<PathBuf>,
+    },
+
+    /// Initialize an operator
+    InitOperator {
+        /// Operator fee in basis points
+        #[arg(long, default_value_t = 0)]
+        operator_fee_bps: u16,
+
+        /// Path to the base keypair, a new one is generated if omitted
+        #[arg(long)]
+        base_keypair: Option<PathBuf>,
+
+        /// Path to write the generated base keypair to
+        #[arg(long)]
+        base_keypair_out: Option<PathBuf>,
+    },
+
+    /// Initialize the NcnOperatorState between an NCN and an operator
+    InitNcnOperatorState { ncn: Pubkey, operator: Pubkey },
+
+    /// Warm up an operator from the NCN side
+    NcnWarmupOperator { ncn: Pubkey, operator: Pubkey },
+
+    /// Cool down an operator from the NCN side
+    NcnCooldownOperator { ncn: Pubkey, operator: Pubkey },
+
+    /// Warm up an NCN from the operator side
+    OperatorWarmupNcn { ncn: Pubkey, operator: Pubkey },
+
+    /// Cool down an NCN from the operator side
+    OperatorCooldownNcn { ncn: Pubkey, operator: Pubkey },
+
+    /// Initialize the NcnVaultTicket between an NCN and a vault
+    InitNcnVaultTicket { ncn: Pubkey, vault: Pubkey },
+
+    /// Warm up the NcnVaultTicket between an NCN and a vault
+    WarmupNcnVaultTicket { ncn: Pubkey, vault: Pubkey },
+
+    /// Cool down the NcnVaultTicket between an NCN and a vault
+    CooldownNcnVaultTicket { ncn: Pubkey, vault: Pubkey },
+
+    /// Initialize the OperatorVaultTicket between an operator and a vault
+    InitOperatorVaultTicket { operator: Pubkey, vault: Pubkey },
+
+    /// Warm up the OperatorVaultTicket between an operator and a vault
+    WarmupOperatorVaultTicket { operator: Pubkey, vault: Pubkey },
+
+    /// Cool down the OperatorVaultTicket between an operator and a vault
+    CooldownOperatorVaultTicket { operator: Pubkey, vault: Pubkey },
+
+    /// Transfer the NCN admin to a new keypair
+    NcnSetAdmin {
+        ncn: Pubkey,
+
+        /// Path to the new admin keypair, which must sign
+        #[arg(long)]
+        new_admin_keypair: PathBuf,
+    },
+
+    /// Transfer the operator admin to a new keypair
+    OperatorSetAdmin {
+        operator: Pubkey,
+
+        /// Path to the new admin keypair, which must sign
+        #[arg(long)]
+        new_admin_keypair: PathBuf,
+    },
+
+    /// Set the operator fee
+    OperatorSetFee {
+        operator: Pubkey,
+
+        /// New operator fee in basis points
+        #[arg(long)]
+        fee_bps: u16,
+    },
+}
+
+#[derive(Subcommand)]
+enum VaultCommands {
+    /// Initialize a vault with a newly generated VRT mint
+    InitVault {
+        /// Supported token mint
+        st_mint: Pubkey,
+
+        /// Deposit fee in basis points
+        #[arg(long, default_value_t = 0)]
+        deposit_fee_bps: u16,
+
+        /// Withdrawal fee in basis points
+        #[arg(long, default_value_t = 0)]
+        withdrawal_fee_bps: u16,
+
+        /// Reward fee in basis points
+        #[arg(long, default_value_t = 0)]
+        reward_fee_bps: u16,
+
+        /// Decimals of the VRT mint
+        #[arg(long, default_value_t = 9)]
+        decimals: u8,
+
+        /// Path to the base keypair, a new one is generated if omitted
+        #[arg(long)]
+        base_keypair: Option<PathBuf>,
+
+        /// Path to write the generated base keypair to
+        #[arg(long)]
+        base_keypair_out: Option<PathBuf>,
+    },
+
+    /// Initialize the VaultOperatorDelegation between a vault and an operator
+    InitDelegation { vault: Pubkey, operator: Pubkey },
+
+    /// Delegate vault assets to an operator
+    AddDelegation {
+        vault: Pubkey,
+        operator: Pubkey,
+
+        /// Amount of the supported token to delegate
+        #[arg(long)]
+        amount: u64,
+    },
+
+    /// Cool down a delegation to an operator
+    CooldownDelegation {
+        vault: Pubkey,
+        operator: Pubkey,
+
+        /// Amount of the supported token to cool down
+        #[arg(long)]
+        amount: u64,
+    },
+
+    /// Set the deposit capacity of a vault
+    SetDepositCapacity {
+        vault: Pubkey,
+
+        /// Maximum amount of the supported token the vault accepts
+        #[arg(long)]
+        amount: u64,
+    },
+
+    /// Set the fees of a vault, leaving omitted fees unchanged
+    SetFees {
+        vault: Pubkey,
+
+        /// Deposit fee in basis points
+        #[arg(long)]
+        deposit_fee_bps: Option<u16>,
+
+        /// Withdrawal fee in basis points
+        #[arg(long)]
+        withdrawal_fee_bps: Option<u16>,
+
+        /// Reward fee in basis points
+        #[arg(long)]
+        reward_fee_bps: Option<u16>,
+    },
+}
+
+fn read_keypair(path: &Path) -> anyhow::Result<Keypair> {
+    read_keypair_file(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read keypair {}: {e}", path.display()))
+}
+
+/// Prints the signature of a sent transaction, exported ones have none yet.
+fn print_signature(sig: Option<Signature>) {
+    match sig {
+        Some(sig) => println!("Signature: {sig}"),
+        None => eprintln!("Transaction exported, not sent"),
+    }
+}
+
+fn display_budget(budget: Option<u64>) -> String {
+    budget.map_or_else(|| "none".to_string(), |budget| format!("{budget} lamports"))
+}
+
+fn display_option(value: Option<u64>) -> String {
+    value.map_or_else(|| "unknown".to_string(), |value| value.to_string())
+}
+
+/// Runs the next step (initialize, crank or close) of a vault from the subscribed view.
+#[allow(clippy::too_many_arguments)]
+async fn step_vault(
+    rpc_url: &str,
+    commitment: CommitmentConfig,
+    vault_program_id: Pubkey,
+    payers: &Arc<PayerPool>,
+    vault_program_handler: &VaultProgramHandler,
+    history: &Arc<CrankHistory>,
+    nonces: Option<&Arc<NoncePool>>,
+    crank_batch_size: usize,
+    compute: &Arc<ComputeUnitEstimator>,
+    priority_fees: Option<&Arc<PriorityFeeEscalator>>,
+    budget: &SpendingBudget,
+    paused_payers: &HashSet<Pubkey>,
+    cache: &RwLock<AccountCache>,
+    vault_pubkey: Pubkey,
+) -> anyhow::Result<()> {
+    let (vault, delegations) = {
+        let cache = cache.read().await;
+        let Some(vault) = cache.vault(&vault_pubkey) else {
+            return Ok(());
+        };
+        (vault, cache.delegations(&vault_pubkey))
+    };
+
+    // Closing a tracker is not reported through programSubscribe, so one closed by another
+    // cranker is read again before acting on it
+    let mut tracker = None;
+    loop {
+        let cached = cache.read().await.tracker(&vault_pubkey);
+        let Some((tracker_pubkey, cached)) = cached else {
+            break;
+        };
+        tracker = vault_program_handler
+            .get_update_state_tracker(&vault_pubkey, cached.ncn_epoch())
+            .await?;
+        if tracker.is_some() {
+            break;
+        }
+        log::info!("VaultUpdateStateTracker {tracker_pubkey} was closed");
+        cache.write().await.remove_tracker(&tracker_pubkey);
+    }
+
+    let mut vault_state_manager = VaultStateManager::new(
+        rpc_url,
+        commitment,
+        vault_program_id,
+        payers.clone(),
+        (vault_pubkey, vault),
+    );
+    vault_state_manager.set_operator_delegations(&delegations);
+    vault_state_manager.set_history(history.clone());
+    if let Some(nonces) = nonces {
+        vault_state_manager.set_nonce_pool(nonces.clone());
+    }
+    vault_state_manager.set_crank_batch_size(crank_batch_size);
+    vault_state_manager.set_compute_unit_estimator(compute.clone());
+    if let Some(priority_fees) = priority_fees {
+        vault_state_manager.set_priority_fees(priority_fees.clone());
+    }
+    vault_state_manager
+        .set_initialize_paused(paused_payers.contains(&vault_state_manager.payer_pubkey()));
+
+    if let Some(tracker) = tracker {
+        vault_state_manager.set_tracker(tracker);
+    }
+
+    let current_epoch = vault_program_handler.get_current_epoch().await?;
+    let costs = history.costs(current_epoch)?;
+    let overhead = history.overhead_cost(current_epoch)?;
+    let tracker_rent = vault_program_handler.get_tracker_rent().await?;
+    vault_state_manager.set_budget_exhausted(budget.exhausted(
+        &costs,
+        &overhead,
+        &vault_pubkey,
+        spending::update_cost(vault.operator_count(), tracker_rent),
+    ));
+
+    let config = vault_program_handler.get_config().await;
+    let action = vault_state_manager
+        .step(current_epoch, config.epoch_length())
+        .await?;
+    if let (VaultAction::Close | VaultAction::CloseStale, Some(tracker)) = (action, tracker) {
+        cache.write().await.remove_tracker(&tracker.0);
+    }
+
+    Ok(())
 }
 
 #[tokio::main]
@@ -52,111 +582,595 @@ async fn main() -> anyhow::Result<(), anyhow::Error> {
         .init();
 
     let args = Args::parse();
-    let payer = read_keypair_file(args.keypair).expect("read keypair file");
+    let sources = if args.keypairs.is_empty() {
+        vec![SignerSource::CliConfig(None)]
+    } else {
+        args.keypairs.clone()
+    };
+    let mut signers = Vec::with_capacity(sources.len());
+    for source in sources.iter() {
+        let signer = source
+            .load()
+            .await
+            .with_context(|| format!("Failed to load payer from {source}"))?;
+        signers.push(signer);
+    }
+    if args.export.is_none() {
+        if let Some(source) = sources
+            .iter()
+            .find(|source| matches!(source, SignerSource::Pubkey(_)))
+        {
+            anyhow::bail!("Payer {source} can not sign, it can only be used with --export");
+        }
+    }
+    let payers = Arc::new(PayerPool::new(signers, &args.vault_payers)?);
+    let payer = payers.primary().clone();
+
+    let exporter = args
+        .export
+        .map(|format| TransactionExporter::new(format, args.export_out.as_deref()))
+        .transpose()?
+        .map(Arc::new);
+    if exporter.is_some()
+        && matches!(
+            args.commands,
+            Commands::Run { .. } | Commands::Bootstrap { .. }
+        )
+    {
+        anyhow::bail!("--export only applies to crank-vault, restaking and vault commands");
+    }
 
-    let vault_program_handler = VaultProgramHandler::new(&args.rpc_url, args.vault_program_id)
-        .await
-        .expect("Failed to construct VaultProgramHandler");
+    // Shared so every vault benefits from the compute units measured on the others
+    let compute = Arc::new(ComputeUnitEstimator::new(args.compute_unit_margin_percent));
+
+    // Initialize, crank and close use durable nonces, and so do exported transactions, which are
+    // signed long after they are built
+    let exporting = exporter.is_some()
+        && matches!(
+            args.commands,
+            Commands::Restaking { .. } | Commands::Vault { .. } | Commands::CrankVault { .. }
+        );
+    if exporting && args.nonce_accounts == 0 {
+        anyhow::bail!(
+            "Exporting transactions needs --nonce-accounts, a recent blockhash expires before \
+             they can be signed"
+        );
+    }
+
+    let vault_program_handler =
+        VaultProgramHandler::new(&args.rpc_url, args.commitment, args.vault_program_id)
+            .await
+            .expect("Failed to construct VaultProgramHandler");
+
+    let nonces = if args.nonce_accounts > 0
+        && (exporting
+            || matches!(
+                args.commands,
+                Commands::Run { .. } | Commands::CrankVault { .. }
+            )) {
+        let nonces = Arc::new(NoncePool::new(args.nonce_accounts));
+        let rpc_client = RpcClient::new_with_commitment(args.rpc_url.clone(), args.commitment);
+        // Their rent and fees are overhead of the epoch they are created in
+        let history = CrankHistory::open(&args.history_db)?;
+        let current_epoch = vault_program_handler.get_current_epoch().await?;
+        let mut exported = 0;
+        for payer in payers.payers() {
+            let created = nonces
+                .create_missing(
+                    &rpc_client,
+                    &history,
+                    current_epoch,
+                    payer.as_ref(),
+                    exporter.as_deref(),
+                )
+                .await?;
+            if exporter.is_some() {
+                exported += created;
+            }
+        }
+        // Exported crank transactions would refer to nonce accounts that do not exist yet
+        if exported > 0 {
+            println!("Exported the creation of {exported} nonce accounts, submit it and run again");
+            return Ok(());
+        }
+        Some(nonces)
+    } else {
+        None
+    };
+
+    let budget = SpendingBudget {
+        epoch: args.epoch_budget_sol.map(sol_to_lamports),
+        vault: args.vault_epoch_budget_sol.map(sol_to_lamports),
+    };
+
+    // Kept across cycles, so failed sends keep raising the fee of their vault
+    let priority_fees = if args.priority_fee_max > 0
+        && matches!(
+            args.commands,
+            Commands::Run { .. } | Commands::CrankVault { .. }
+        ) {
+        let epoch_length = vault_program_handler.get_config().await.epoch_length();
+        Some(Arc::new(PriorityFeeEscalator::new(
+            PriorityFeeConfig {
+                base: args.priority_fee_base,
+                max: args.priority_fee_max,
+                failure_step_percent: args.priority_fee_failure_step_percent,
+            },
+            epoch_length,
+        )))
+    } else {
+        None
+    };
 
     match args.commands {
-        Commands::Run => {
+        Commands::Run {
+            subscribe,
+            rescan_interval_secs,
+            warn_balance_sol,
+            min_balance_sol,
+            webhook_url,
+            alert_dedup_secs,
+            alert_rate_limit,
+            alert_after_failures,
+            metrics_file,
+        } => {
+            let history = Arc::new(CrankHistory::open(&args.history_db)?);
+            let interrupted = history.interrupt_planned()?;
+            for record in interrupted.iter() {
+                log::warn!(
+                    "Resuming vault {} after {} of epoch {} was interrupted",
+                    record.vault,
+                    record.action,
+                    record.ncn_epoch
+                );
+            }
+            history.record_budget(&budget)?;
+
+            // Vaults whose action was interrupted go first in the first cycle
+            let mut resume: HashSet<Pubkey> =
+                interrupted.iter().map(|record| record.vault).collect();
+
+            let payer_thresholds = PayerThresholds {
+                warn: sol_to_lamports(warn_balance_sol),
+                floor: sol_to_lamports(min_balance_sol),
+            };
+            // Payers below the floor, whose initializations are paused
+            let mut paused_payers: HashSet<Pubkey> = HashSet::new();
+
+            let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+            if let Some(webhook_url) = webhook_url.as_deref() {
+                notifiers.push(Box::new(WebhookNotifier::new(webhook_url)?));
+            }
+            let mut alerter = Alerter::new(
+                notifiers,
+                Duration::from_secs(alert_dedup_secs),
+                alert_rate_limit,
+                Duration::from_secs(60 * 60),
+            );
+            let mut consecutive_failures = 0;
+            // Missed epochs are checked when the loop sees a new epoch
+            let mut last_cycle_epoch: Option<u64> = None;
+
+            let cache = Arc::new(RwLock::new(AccountCache::default()));
+            let mut receiver = None;
+            if subscribe {
+                let ws_url = args
+                    .ws_url
+                    .clone()
+                    .unwrap_or_else(|| websocket_url(&args.rpc_url));
+                let subscriber =
+                    AccountSubscriber::new(&ws_url, args.commitment, args.vault_program_id);
+                let (sender, rx) = mpsc::unbounded_channel();
+                let cache = cache.clone();
+                tokio::spawn(async move { subscriber.subscribe(cache, sender).await });
+                receiver = Some(rx);
+            }
+
             loop {
-                let vaults: HashMap<Pubkey, Vault> = vault_program_handler.get_vaults().await?;
-                let vault_operator_delegations = vault_program_handler
-                    .get_vault_operator_delegations()
-                    .await?;
-                let trackers = vault_program_handler.get_update_state_trackers().await?;
-
-                let mut grouped_delegations: HashMap<
-                    Pubkey,
-                    Vec<(Pubkey, VaultOperatorDelegation)>,
-                > = HashMap::new();
-                for (pubkey, delegation) in vault_operator_delegations {
-                    grouped_delegations
-                        .entry(delegation.vault)
-                        .or_default()
-                        .push((pubkey, delegation));
-                }
-
-                let mut manager_map = HashMap::new();
-                for (vault_pubkey, vault) in vaults.iter() {
-                    let mut vault_state_manager = VaultStateManager::new(
-                        &args.rpc_url,
-                        args.vault_program_id,
-                        &payer,
-                        (*vault_pubkey, *vault),
-                    );
+                let cycle: anyhow::Result<()> = async {
+                    let config = vault_program_handler.get_config().await;
+                    let ProgramSnapshot {
+                        slot,
+                        vaults,
+                        delegations: vault_operator_delegations,
+                        trackers,
+                    } = vault_program_handler
+                        .get_snapshot(config.epoch_length())
+                        .await?;
+                    log::info!("Read vault program accounts at slot {slot}");
 
-                    // Trackers
-                    if let Some(tracker) = trackers.get(vault_pubkey) {
-                        vault_state_manager.set_tracker(*tracker);
+                    if receiver.is_some() {
+                        // Full rescans keep the subscribed view consistent
+                        cache.write().await.replace(
+                            &vaults,
+                            &vault_operator_delegations,
+                            &trackers,
+                        );
                     }
 
-                    // VaultOperatorDelegations
-                    if let Some(operator_delegations) = grouped_delegations.get(vault_pubkey) {
-                        vault_state_manager.set_operator_delegations(operator_delegations);
+                    let mut grouped_delegations: HashMap<
+                        Pubkey,
+                        Vec<(Pubkey, VaultOperatorDelegation)>,
+                    > = HashMap::new();
+                    for (pubkey, delegation) in vault_operator_delegations {
+                        grouped_delegations
+                            .entry(delegation.vault)
+                            .or_default()
+                            .push((pubkey, delegation));
                     }
 
-                    manager_map
-                        .entry(vault_pubkey)
-                        .or_insert(vault_state_manager);
-                }
+                    let current_epoch = slot / config.epoch_length();
 
-                match vaults.len().cmp(&trackers.len()) {
-                    Ordering::Less => {}
-                    Ordering::Equal => {
-                        // All vaults are tracked
-                        log::info!("All vaults are tracked");
+                    let mut action_counts: HashMap<VaultAction, usize> = HashMap::new();
+                    let mut ordered_vaults: Vec<_> = vaults.iter().collect();
+                    ordered_vaults
+                        .sort_by_key(|(vault_pubkey, _vault)| !resume.contains(vault_pubkey));
+                    resume.clear();
 
-                        let mut cranking_vaults = Vec::new();
-                        for (vault_pubkey, vault) in vaults.iter() {
-                            if let Some(tracker) = trackers.get(vault_pubkey) {
-                                if tracker.1.last_updated_index() != vault.operator_count() {
-                                    // crank
-                                    cranking_vaults.push(vault_pubkey);
-                                }
-                            }
+                    let mut managers = Vec::with_capacity(ordered_vaults.len());
+                    for (vault_pubkey, vault) in ordered_vaults {
+                        let mut vault_state_manager = VaultStateManager::new(
+                            &args.rpc_url,
+                            args.commitment,
+                            args.vault_program_id,
+                            payers.clone(),
+                            (*vault_pubkey, *vault),
+                        );
+
+                        // Trackers
+                        if let Some(tracker) = trackers.get(vault_pubkey) {
+                            vault_state_manager.set_tracker(*tracker);
                         }
 
-                        log::info!(
-                            "The vaults which should be cranked: {}",
-                            cranking_vaults.len()
-                        );
+                        // VaultOperatorDelegations
+                        if let Some(operator_delegations) = grouped_delegations.get(vault_pubkey) {
+                            vault_state_manager.set_operator_delegations(operator_delegations);
+                        }
+                        vault_state_manager.set_history(history.clone());
+                        if let Some(nonces) = nonces.as_ref() {
+                            vault_state_manager.set_nonce_pool(nonces.clone());
+                        }
+                        vault_state_manager.set_crank_batch_size(args.crank_batch_size);
+                        vault_state_manager.set_compute_unit_estimator(compute.clone());
+                        if let Some(priority_fees) = priority_fees.as_ref() {
+                            vault_state_manager.set_priority_fees(priority_fees.clone());
+                        }
+                        managers.push(vault_state_manager);
+                    }
 
-                        if !cranking_vaults.is_empty() {
-                            for cranking_vault_pubkey in cranking_vaults {
-                                if let Some(manager) = manager_map.get(&cranking_vault_pubkey) {
-                                    manager.crank().await?;
-                                }
+                    // ---------- PAYER BALANCES ----------
+                    let tracker_rent = vault_program_handler.get_tracker_rent().await?;
+                    let mut planned_actions: HashMap<Pubkey, Vec<(VaultAction, u64)>> =
+                        HashMap::new();
+                    for manager in managers.iter() {
+                        planned_actions
+                            .entry(manager.payer_pubkey())
+                            .or_default()
+                            .push((
+                                manager.next_action(current_epoch, config.epoch_length()),
+                                manager.operator_count(),
+                            ));
+                    }
+
+                    paused_payers.clear();
+                    for payer in payers.payers() {
+                        let payer = payer.pubkey();
+                        let balance = vault_program_handler.get_balance(&payer).await?;
+                        let estimated_cost = estimate_cost(
+                            planned_actions
+                                .get(&payer)
+                                .map(Vec::as_slice)
+                                .unwrap_or_default(),
+                            tracker_rent,
+                        );
+                        let balance_sol = lamports_to_sol(balance);
+                        let estimated_cost_sol = lamports_to_sol(estimated_cost);
+                        let payer_status = payer_thresholds.status(balance, estimated_cost);
+                        match payer_status {
+                            PayerStatus::Healthy => log::info!(
+                                "Payer {payer} balance: {balance_sol} SOL, cycle cost: {} SOL",
+                                estimated_cost_sol
+                            ),
+                            PayerStatus::Low => log::warn!(
+                                "Payer {payer} balance low: {balance_sol} SOL, cycle cost: {} SOL",
+                                estimated_cost_sol
+                            ),
+                            PayerStatus::BelowFloor => {
+                                log::error!(
+                                    "Payer {payer} balance {} SOL below floor, pausing its \
+                                     initializations",
+                                    balance_sol
+                                );
+                                paused_payers.insert(payer);
                             }
+                        }
+                        if payer_status == PayerStatus::Healthy {
+                            alerter.resolve(&format!("payer_low:{payer}"));
                         } else {
-                            // Close
-                            for manager in manager_map.values() {
-                                manager.close().await?;
-                            }
+                            alerter
+                                .fire(Alert::PayerLow {
+                                    payer,
+                                    balance,
+                                    estimated_cost,
+                                    below_floor: payer_status == PayerStatus::BelowFloor,
+                                })
+                                .await;
                         }
                     }
-                    Ordering::Greater => {
-                        // Initialize
-                        let current_epoch = vault_program_handler.get_current_epoch().await?;
-                        let config = vault_program_handler.get_config().await;
-                        let mut count = 0;
-                        for manager in manager_map.values() {
-                            if !manager.is_tracked()
-                                && manager.is_update_needed(current_epoch, config.epoch_length())
+
+                    // ---------- MISSED EPOCHS ----------
+                    if last_cycle_epoch.is_some_and(|epoch| epoch < current_epoch) {
+                        for manager in managers.iter() {
+                            if let Some(ncn_epoch) =
+                                manager.missed_epoch(current_epoch, config.epoch_length())
                             {
-                                manager.initialize(current_epoch).await?;
-                                count += 1;
+                                alerter
+                                    .fire(Alert::VaultMissedEpoch {
+                                        vault: manager.vault_pubkey(),
+                                        ncn_epoch,
+                                    })
+                                    .await;
+                            }
+                        }
+                    }
+                    last_cycle_epoch = Some(current_epoch);
+
+                    // A failing vault does not hold up the others
+                    let mut step_error = None;
+                    for mut manager in managers {
+                        manager
+                            .set_initialize_paused(paused_payers.contains(&manager.payer_pubkey()));
+                        // Read again for every vault, as the ones stepped before spent some
+                        let costs = history.costs(current_epoch)?;
+                        let overhead = history.overhead_cost(current_epoch)?;
+                        manager.set_budget_exhausted(budget.exhausted(
+                            &costs,
+                            &overhead,
+                            &manager.vault_pubkey(),
+                            spending::update_cost(manager.operator_count(), tracker_rent),
+                        ));
+                        match manager.step(current_epoch, config.epoch_length()).await {
+                            Ok(action) => *action_counts.entry(action).or_default() += 1,
+                            Err(e) => {
+                                log::error!(
+                                    "Failed to step vault {}: {e:?}",
+                                    manager.vault_pubkey()
+                                );
+                                step_error = Some(e);
                             }
                         }
+                    }
 
-                        log::info!("Initialize {count} vaults");
+                    log::info!("Epoch {current_epoch} actions: {action_counts:?}");
+
+                    // ---------- SPENDING ----------
+                    let costs = history.costs(current_epoch)?;
+                    let overhead = history.overhead_cost(current_epoch)?;
+                    let spent: i64 = costs.iter().map(VaultCost::total).sum();
+                    log::info!(
+                        "Epoch {current_epoch} spending: {spent} lamports on {} vaults, {} on \
+                         overhead",
+                        costs.len(),
+                        overhead.total()
+                    );
+                    if let Some(metrics_file) = metrics_file.as_deref() {
+                        if let Err(e) = spending::write_metrics(
+                            metrics_file,
+                            current_epoch,
+                            &costs,
+                            &overhead,
+                            &budget,
+                        ) {
+                            log::error!("Failed to write metrics: {e:?}");
+                        }
+                    }
+
+                    if let Some(e) = step_error {
+                        return Err(e);
                     }
+
+                    Ok(())
                 }
+                .await;
 
-                // ---------- SLEEP (1 hour)----------
-                tokio::time::sleep(Duration::from_secs(60 * 60)).await;
+                let next_cycle_in = match cycle {
+                    Ok(()) => {
+                        consecutive_failures = 0;
+                        alerter.resolve("loop_failing");
+                        Duration::from_secs(rescan_interval_secs)
+                    }
+                    Err(e) => {
+                        consecutive_failures += 1;
+                        log::error!("Cycle failed ({consecutive_failures} in a row): {e:?}");
+                        if consecutive_failures >= alert_after_failures {
+                            alerter
+                                .fire(Alert::LoopFailing {
+                                    consecutive_failures,
+                                    error: format!("{e:#}"),
+                                })
+                                .await;
+                        }
+                        FAILED_CYCLE_RETRY_DELAY.min(Duration::from_secs(rescan_interval_secs))
+                    }
+                };
+
+                // ---------- SLEEP (until the next rescan) ----------
+                let deadline = Instant::now() + next_cycle_in;
+                let Some(receiver) = receiver.as_mut() else {
+                    tokio::time::sleep_until(deadline).await;
+                    continue;
+                };
+
+                loop {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(deadline) => break,
+                        Some(vault_pubkey) = receiver.recv() => {
+                            let mut pending = HashSet::from([vault_pubkey]);
+                            while let Ok(vault_pubkey) = receiver.try_recv() {
+                                pending.insert(vault_pubkey);
+                            }
+
+                            for vault_pubkey in pending {
+                                if let Err(e) = step_vault(
+                                    &args.rpc_url,
+                                    args.commitment,
+                                    args.vault_program_id,
+                                    &payers,
+                                    &vault_program_handler,
+                                    &history,
+                                    nonces.as_ref(),
+                                    args.crank_batch_size,
+                                    &compute,
+                                    priority_fees.as_ref(),
+                                    &budget,
+                                    &paused_payers,
+                                    &cache,
+                                    vault_pubkey,
+                                )
+                                .await
+                                {
+                                    log::error!("Failed to step vault {vault_pubkey}: {e:?}");
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Commands::Status { epoch } => {
+            let epoch = match epoch {
+                Some(epoch) => epoch,
+                None => vault_program_handler.get_current_epoch().await?,
+            };
+            let history = CrankHistory::open(&args.history_db)?;
+            let costs = history.costs(epoch)?;
+            let overhead = history.overhead_cost(epoch)?;
+
+            // The budgets given on this command line, or else the ones the last run enforced
+            let (budget, source) = if budget != SpendingBudget::default() {
+                (budget, "given on the command line")
+            } else if let Some(budget) = history.budget()? {
+                (budget, "of the last run")
+            } else {
+                (budget, "none recorded")
+            };
+
+            println!("NCN epoch {epoch}");
+            println!(
+                "Budgets ({source}): epoch: {}, vault: {}",
+                display_budget(budget.epoch),
+                display_budget(budget.vault)
+            );
+            for cost in costs.iter() {
+                print!(
+                    "  {} {} transactions, fees: {}, priority fees: {}, rent: {}, spent: {}",
+                    cost.vault,
+                    cost.transactions,
+                    cost.fees,
+                    cost.priority_fees,
+                    cost.rent,
+                    cost.total()
+                );
+                if let Some(vault_budget) = budget.vault {
+                    print!(", left: {}", vault_budget as i64 - cost.total());
+                }
+                println!();
+            }
+            println!(
+                "  overhead (nonce accounts) {} transactions, fees: {}, rent: {}, spent: {}",
+                overhead.transactions,
+                overhead.fees,
+                overhead.rent,
+                overhead.total()
+            );
+
+            let spent: i64 = costs.iter().map(VaultCost::total).sum::<i64>() + overhead.total();
+            print!(
+                "Spent {spent} lamports on {} vaults and overhead",
+                costs.len()
+            );
+            if let Some(epoch_budget) = budget.epoch {
+                print!(", {} left of {epoch_budget}", epoch_budget as i64 - spent);
             }
+            println!();
+
+            Ok(())
+        }
+        Commands::History {
+            vault,
+            epoch,
+            status,
+            limit,
+        } => {
+            let history = CrankHistory::open(&args.history_db)?;
+            let records = history.query(&HistoryFilter {
+                vault,
+                ncn_epoch: epoch,
+                status,
+                limit: Some(limit),
+            })?;
+
+            for record in records {
+                print!(
+                    "#{} {} vault {} epoch {} {} {}",
+                    record.id,
+                    record.planned_at,
+                    record.vault,
+                    record.ncn_epoch,
+                    record.action,
+                    record.status
+                );
+                if let Some(duration_ms) = record.duration_ms {
+                    print!(" in {duration_ms} ms");
+                }
+                println!();
+                if let Some(error) = record.error {
+                    println!("  Error: {error}");
+                }
+                for transaction in record.transactions {
+                    println!(
+                        "  {} slot: {}, fee: {}, compute units: {}, compute unit price: {}",
+                        transaction.signature,
+                        display_option(transaction.slot),
+                        display_option(transaction.fee),
+                        display_option(transaction.compute_units),
+                        transaction.compute_unit_price.unwrap_or_default()
+                    );
+                }
+            }
+
+            Ok(())
+        }
+        Commands::Report {
+            epoch,
+            format,
+            output,
+        } => {
+            let history = CrankHistory::open(&args.history_db)?;
+            let records = history.query(&HistoryFilter {
+                ncn_epoch: Some(epoch),
+                ..HistoryFilter::default()
+            })?;
+            let vaults = vault_program_handler.get_vaults().await?;
+            let config = vault_program_handler.get_config().await;
+
+            let current_epoch = vault_program_handler.get_current_epoch().await?;
+
+            let report = EpochReport::new(
+                epoch,
+                current_epoch,
+                config.epoch_length(),
+                &vaults,
+                &records,
+            );
+            let rendered = report.render(format)?;
+            match output {
+                Some(output) => std::fs::write(&output, rendered)
+                    .with_context(|| format!("Failed to write {}", output.display()))?,
+                None => print!("{rendered}"),
+            }
+
+            Ok(())
         }
         Commands::GetVaultUpdateStateTrackers => {
             let trackers = vault_program_handler
@@ -165,6 +1179,418 @@ async fn main() -> anyhow::Result<(), anyhow::Error> {
                 .unwrap();
             println!("{:?}", trackers);
 
+            Ok(())
+        }
+        Commands::CrankVault {
+            vault: vault_pubkey,
+            epoch,
+            skip_initialize,
+            skip_crank,
+            skip_close,
+        } => {
+            let vault = vault_program_handler.get_vault(&vault_pubkey).await?;
+            let config = vault_program_handler.get_config().await;
+            let current_epoch = vault_program_handler.get_current_epoch().await?;
+            let epoch = epoch.unwrap_or(current_epoch);
+            if epoch != current_epoch && !(skip_initialize && skip_crank) {
+                anyhow::bail!(
+                    "Only trackers of the current epoch {current_epoch} can be initialized and \
+                     cranked, pass --skip-initialize and --skip-crank to close the tracker of \
+                     epoch {epoch}"
+                );
+            }
+            let history = Arc::new(CrankHistory::open(&args.history_db)?);
+
+            let delegations = vault_program_handler
+                .get_vault_operator_delegations_by_vault(&vault_pubkey)
+                .await?;
+
+            println!("Vault: {vault_pubkey}");
+            println!("  Operator count: {}", vault.operator_count());
+            println!("  Delegations found: {}", delegations.len());
+            println!(
+                "  Last full state update slot: {}",
+                vault.last_full_state_update_slot()
+            );
+            println!("  Current epoch: {current_epoch}, target epoch: {epoch}");
+
+            let mut vault_state_manager = VaultStateManager::new(
+                &args.rpc_url,
+                args.commitment,
+                args.vault_program_id,
+                payers.clone(),
+                (vault_pubkey, vault),
+            );
+            vault_state_manager.set_operator_delegations(&delegations);
+            // Records the actions and the payer of the tracker, so a later close refunds it
+            vault_state_manager.set_history(history);
+            let exporting = exporter.is_some();
+            if let Some(exporter) = exporter {
+                vault_state_manager.set_exporter(exporter);
+            }
+            if let Some(nonces) = nonces {
+                vault_state_manager.set_nonce_pool(nonces);
+            }
+            vault_state_manager.set_crank_batch_size(args.crank_batch_size);
+            vault_state_manager.set_compute_unit_estimator(compute);
+            if let Some(priority_fees) = priority_fees {
+                vault_state_manager.set_priority_fees(priority_fees);
+            }
+
+            if !vault_state_manager.is_update_needed(epoch, config.epoch_length()) {
+                println!("  Vault is already up to date for epoch {epoch}");
+            }
+
+            if skip_initialize {
+                println!("Skipping initialize");
+            } else {
+                println!("Initializing VaultUpdateStateTracker for epoch {epoch}");
+                vault_state_manager
+                    .run(VaultAction::Initialize, epoch, current_epoch)
+                    .await?;
+            }
+
+            match vault_program_handler
+                .get_update_state_tracker(&vault_pubkey, epoch)
+                .await?
+            {
+                Some(tracker) => {
+                    println!(
+                        "VaultUpdateStateTracker: {}, ncn_epoch: {}, last_updated_index: {}",
+                        tracker.0,
+                        tracker.1.ncn_epoch(),
+                        tracker.1.last_updated_index()
+                    );
+                    vault_state_manager.set_tracker(tracker);
+                }
+                None => {
+                    println!("No VaultUpdateStateTracker found for epoch {epoch}");
+                    if exporting && !skip_initialize {
+                        println!("Submit the initialization, then export the crank and close");
+                    }
+                    return Ok(());
+                }
+            }
+
+            let action = vault_state_manager.next_action(current_epoch, config.epoch_length());
+            println!("  Pre-flight action: {action:?}");
+
+            if skip_crank {
+                println!("Skipping crank");
+            } else if action != VaultAction::Crank {
+                println!("Skipping crank, the tracker can not be cranked");
+            } else {
+                println!("Cranking {} delegations", delegations.len());
+                vault_state_manager
+                    .run(VaultAction::Crank, epoch, current_epoch)
+                    .await?;
+            }
+
+            if skip_close {
+                println!("Skipping close");
+            } else {
+                println!("Closing VaultUpdateStateTracker");
+                let close = if epoch < current_epoch {
+                    VaultAction::CloseStale
+                } else {
+                    VaultAction::Close
+                };
+                vault_state_manager.run(close, epoch, current_epoch).await?;
+            }
+
+            println!("Done");
+
+            Ok(())
+        }
+        Commands::Restaking { action } => {
+            let mut restaking_handler = RestakingHandler::new(
+                &args.rpc_url,
+                args.commitment,
+                payer,
+                args.restaking_program_id,
+                args.vault_program_id,
+            );
+            if let Some(exporter) = exporter {
+                restaking_handler.set_exporter(exporter);
+            }
+            if let Some(nonces) = nonces {
+                restaking_handler.set_nonce_pool(nonces);
+            }
+            restaking_handler.set_compute_unit_estimator(compute);
+
+            match action {
+                RestakingCommands::InitConfig => {
+                    let sig = restaking_handler.initialize_config().await?;
+                    println!("Config: {}", restaking_handler.config_address());
+                    print_signature(sig);
+                }
+                RestakingCommands::InitNcn {
+                    base_keypair,
+                    base_keypair_out,
+                } => {
+                    let base = base_keypair.as_deref().map(read_keypair).transpose()?;
+                    let (ncn, sig) = restaking_handler
+                        .initialize_ncn(base.as_ref(), base_keypair_out.as_deref())
+                        .await?;
+                    println!("Ncn: {ncn}");
+                    print_signature(sig);
+                }
+                RestakingCommands::InitOperator {
+                    operator_fee_bps,
+                    base_keypair,
+                    base_keypair_out,
+                } => {
+                    let base = base_keypair.as_deref().map(read_keypair).transpose()?;
+                    let (operator, sig) = restaking_handler
+                        .initialize_operator(
+                            operator_fee_bps,
+                            base.as_ref(),
+                            base_keypair_out.as_deref(),
+                        )
+                        .await?;
+                    println!("Operator: {operator}");
+                    print_signature(sig);
+                }
+                RestakingCommands::InitNcnOperatorState { ncn, operator } => {
+                    let (ncn_operator_state, sig) = restaking_handler
+                        .initialize_ncn_operator_state(ncn, operator)
+                        .await?;
+                    println!("NcnOperatorState: {ncn_operator_state}");
+                    print_signature(sig);
+                }
+                RestakingCommands::NcnWarmupOperator { ncn, operator } => {
+                    let sig = restaking_handler.ncn_warmup_operator(ncn, operator).await?;
+                    print_signature(sig);
+                }
+                RestakingCommands::NcnCooldownOperator { ncn, operator } => {
+                    let sig = restaking_handler
+                        .ncn_cooldown_operator(ncn, operator)
+                        .await?;
+                    print_signature(sig);
+                }
+                RestakingCommands::OperatorWarmupNcn { ncn, operator } => {
+                    let sig = restaking_handler.operator_warmup_ncn(ncn, operator).await?;
+                    print_signature(sig);
+                }
+                RestakingCommands::OperatorCooldownNcn { ncn, operator } => {
+                    let sig = restaking_handler
+                        .operator_cooldown_ncn(ncn, operator)
+                        .await?;
+                    print_signature(sig);
+                }
+                RestakingCommands::InitNcnVaultTicket { ncn, vault } => {
+                    let (ncn_vault_ticket, sig) = restaking_handler
+                        .initialize_ncn_vault_ticket(ncn, vault)
+                        .await?;
+                    println!("NcnVaultTicket: {ncn_vault_ticket}");
+                    print_signature(sig);
+                }
+                RestakingCommands::WarmupNcnVaultTicket { ncn, vault } => {
+                    let sig = restaking_handler
+                        .warmup_ncn_vault_ticket(ncn, vault)
+                        .await?;
+                    print_signature(sig);
+                }
+                RestakingCommands::CooldownNcnVaultTicket { ncn, vault } => {
+                    let sig = restaking_handler
+                        .cooldown_ncn_vault_ticket(ncn, vault)
+                        .await?;
+                    print_signature(sig);
+                }
+                RestakingCommands::InitOperatorVaultTicket { operator, vault } => {
+                    let (operator_vault_ticket, sig) = restaking_handler
+                        .initialize_operator_vault_ticket(operator, vault)
+                        .await?;
+                    println!("OperatorVaultTicket: {operator_vault_ticket}");
+                    print_signature(sig);
+                }
+                RestakingCommands::WarmupOperatorVaultTicket { operator, vault } => {
+                    let sig = restaking_handler
+                        .warmup_operator_vault_ticket(operator, vault)
+                        .await?;
+                    print_signature(sig);
+                }
+                RestakingCommands::CooldownOperatorVaultTicket { operator, vault } => {
+                    let sig = restaking_handler
+                        .cooldown_operator_vault_ticket(operator, vault)
+                        .await?;
+                    print_signature(sig);
+                }
+                RestakingCommands::NcnSetAdmin {
+                    ncn,
+                    new_admin_keypair,
+                } => {
+                    let new_admin = read_keypair(&new_admin_keypair)?;
+                    let sig = restaking_handler.ncn_set_admin(ncn, &new_admin).await?;
+                    print_signature(sig);
+                }
+                RestakingCommands::OperatorSetAdmin {
+                    operator,
+                    new_admin_keypair,
+                } => {
+                    let new_admin = read_keypair(&new_admin_keypair)?;
+                    let sig = restaking_handler
+                        .operator_set_admin(operator, &new_admin)
+                        .await?;
+                    print_signature(sig);
+                }
+                RestakingCommands::OperatorSetFee { operator, fee_bps } => {
+                    let sig = restaking_handler
+                        .operator_set_fee(operator, fee_bps)
+                        .await?;
+                    print_signature(sig);
+                }
+            }
+
+            Ok(())
+        }
+        Commands::Vault { action } => {
+            let mut vault_admin_handler = VaultAdminHandler::new(
+                &args.rpc_url,
+                args.commitment,
+                payer,
+                args.restaking_program_id,
+                args.vault_program_id,
+            );
+            if let Some(exporter) = exporter {
+                vault_admin_handler.set_exporter(exporter);
+            }
+            if let Some(nonces) = nonces {
+                vault_admin_handler.set_nonce_pool(nonces);
+            }
+            vault_admin_handler.set_compute_unit_estimator(compute);
+
+            match action {
+                VaultCommands::InitVault {
+                    st_mint,
+                    deposit_fee_bps,
+                    withdrawal_fee_bps,
+                    reward_fee_bps,
+                    decimals,
+                    base_keypair,
+                    base_keypair_out,
+                } => {
+                    let base = base_keypair.as_deref().map(read_keypair).transpose()?;
+                    let (vault, vrt_mint, sig) = vault_admin_handler
+                        .initialize_vault(
+                            st_mint,
+                            deposit_fee_bps,
+                            withdrawal_fee_bps,
+                            reward_fee_bps,
+                            decimals,
+                            base.as_ref(),
+                            base_keypair_out.as_deref(),
+                        )
+                        .await?;
+                    println!("Vault: {vault}");
+                    println!("VRT mint: {vrt_mint}");
+                    print_signature(sig);
+                }
+                VaultCommands::InitDelegation { vault, operator } => {
+                    let (vault_operator_delegation, sig) = vault_admin_handler
+                        .initialize_vault_operator_delegation(vault, operator)
+                        .await?;
+                    println!("VaultOperatorDelegation: {vault_operator_delegation}");
+                    print_signature(sig);
+                }
+                VaultCommands::AddDelegation {
+                    vault,
+                    operator,
+                    amount,
+                } => {
+                    let sig = vault_admin_handler
+                        .add_delegation(vault, operator, amount)
+                        .await?;
+                    print_signature(sig);
+                }
+                VaultCommands::CooldownDelegation {
+                    vault,
+                    operator,
+                    amount,
+                } => {
+                    let sig = vault_admin_handler
+                        .cooldown_delegation(vault, operator, amount)
+                        .await?;
+                    print_signature(sig);
+                }
+                VaultCommands::SetDepositCapacity { vault, amount } => {
+                    let sig = vault_admin_handler
+                        .set_deposit_capacity(vault, amount)
+                        .await?;
+                    print_signature(sig);
+                }
+                VaultCommands::SetFees {
+                    vault,
+                    deposit_fee_bps,
+                    withdrawal_fee_bps,
+                    reward_fee_bps,
+                } => {
+                    let sig = vault_admin_handler
+                        .set_fees(vault, deposit_fee_bps, withdrawal_fee_bps, reward_fee_bps)
+                        .await?;
+                    print_signature(sig);
+                }
+            }
+
+            Ok(())
+        }
+        Commands::Submit { file } => {
+            let input = match file.as_ref() {
+                Some(file) => std::fs::read_to_string(file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?,
+                None => std::io::read_to_string(std::io::stdin())
+                    .context("Failed to read transactions from stdin")?,
+            };
+            let rpc_client = RpcClient::new_with_commitment(args.rpc_url.clone(), args.commitment);
+
+            for (index, line) in input.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let tx = transaction_export::decode_transaction(line)
+                    .with_context(|| format!("Invalid transaction on line {}", index + 1))?;
+                let sig = transaction_export::submit_transaction(&rpc_client, &tx)
+                    .await
+                    .with_context(|| format!("Failed to submit line {}", index + 1))?;
+                println!("Signature: {sig}");
+            }
+
+            Ok(())
+        }
+        Commands::Bootstrap {
+            topology,
+            out_dir,
+            no_wait,
+            allow_remote,
+        } => {
+            if !allow_remote && !bootstrap::is_local_rpc_url(&args.rpc_url) {
+                anyhow::bail!(
+                    "Refusing to bootstrap against {}, pass --allow-remote to override",
+                    args.rpc_url
+                );
+            }
+
+            let topology = Topology::from_file(&topology)?;
+            let bootstrap = Bootstrap::new(
+                &args.rpc_url,
+                args.commitment,
+                payer,
+                args.restaking_program_id,
+                args.vault_program_id,
+                &out_dir,
+            )
+            .await?;
+            let (manifest, manifest_path) = bootstrap.run(&topology, !no_wait).await?;
+
+            println!("{}", serde_json::to_string_pretty(&manifest)?);
+            println!("Manifest written to {}", manifest_path.display());
+            if !manifest.complete {
+                println!(
+                    "Tickets were warmed up, run bootstrap again after the next restaking epoch \
+                     to create vault NCN tickets and delegations"
+                );
+            }
+
             Ok(())
         }
     }
@@ -0,0 +1,734 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::Context;
+use jito_bytemuck::AccountDeserialize;
+use jito_restaking_core::{
+    ncn::Ncn, ncn_operator_state::NcnOperatorState, ncn_vault_ticket::NcnVaultTicket,
+    operator::Operator, operator_vault_ticket::OperatorVaultTicket,
+};
+use jito_vault_core::{
+    vault::Vault, vault_ncn_ticket::VaultNcnTicket,
+    vault_operator_delegation::VaultOperatorDelegation,
+};
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    pubkey,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, write_keypair_file, Keypair},
+    signer::Signer,
+};
+
+use crate::{
+    restaking_handler::RestakingHandler, signer::SharedSigner,
+    vault_admin_handler::VaultAdminHandler, vault_program_handler::VaultProgramHandler,
+};
+
+/// Wrapped SOL mint, which every test validator has, used when a vault has no `st_mint`.
+const NATIVE_MINT: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
+
+fn default_decimals() -> u8 {
+    9
+}
+
+/// Declarative description of the accounts to create, referencing each other by name.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Topology {
+    /// Program fee of the vault config in basis points
+    #[serde(default)]
+    pub vault_program_fee_bps: u16,
+
+    #[serde(default)]
+    pub ncns: Vec<String>,
+
+    #[serde(default)]
+    pub operators: Vec<OperatorSpec>,
+
+    #[serde(default)]
+    pub vaults: Vec<VaultSpec>,
+
+    #[serde(default)]
+    pub ncn_operators: Vec<NcnOperatorSpec>,
+
+    #[serde(default)]
+    pub ncn_vaults: Vec<NcnVaultSpec>,
+
+    #[serde(default)]
+    pub operator_vaults: Vec<OperatorVaultSpec>,
+
+    #[serde(default)]
+    pub delegations: Vec<DelegationSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OperatorSpec {
+    pub name: String,
+
+    #[serde(default)]
+    pub fee_bps: u16,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VaultSpec {
+    pub name: String,
+
+    /// Supported token mint (base58), defaults to the wrapped SOL mint
+    pub st_mint: Option<String>,
+
+    #[serde(default)]
+    pub deposit_fee_bps: u16,
+
+    #[serde(default)]
+    pub withdrawal_fee_bps: u16,
+
+    #[serde(default)]
+    pub reward_fee_bps: u16,
+
+    #[serde(default = "default_decimals")]
+    pub decimals: u8,
+
+    pub deposit_capacity: Option<u64>,
+}
+
+impl VaultSpec {
+    fn st_mint(&self) -> anyhow::Result<Pubkey> {
+        match &self.st_mint {
+            Some(st_mint) => Pubkey::from_str(st_mint)
+                .with_context(|| format!("Invalid st_mint for vault {}: {st_mint}", self.name)),
+            None => Ok(NATIVE_MINT),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NcnOperatorSpec {
+    pub ncn: String,
+    pub operator: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NcnVaultSpec {
+    pub ncn: String,
+    pub vault: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OperatorVaultSpec {
+    pub operator: String,
+    pub vault: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DelegationSpec {
+    pub vault: String,
+    pub operator: String,
+
+    /// Amount to delegate, nothing is delegated when zero
+    #[serde(default)]
+    pub amount: u64,
+}
+
+impl Topology {
+    /// Reads a topology from a JSON file and validates it.
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read topology file {}", path.display()))?;
+        let topology: Self = serde_json::from_str(&data)
+            .with_context(|| format!("Failed to parse topology file {}", path.display()))?;
+        topology.validate()?;
+
+        Ok(topology)
+    }
+
+    /// Checks that names are unique and every reference points to a declared account.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        fn unique<'a>(
+            kind: &str,
+            names: impl Iterator<Item = &'a String>,
+        ) -> anyhow::Result<HashSet<&'a str>> {
+            let mut set = HashSet::new();
+            for name in names {
+                if !set.insert(name.as_str()) {
+                    anyhow::bail!("Duplicate {kind} name: {name}");
+                }
+            }
+            Ok(set)
+        }
+
+        fn known(kind: &str, names: &HashSet<&str>, name: &str) -> anyhow::Result<()> {
+            if !names.contains(name) {
+                anyhow::bail!("Unknown {kind}: {name}");
+            }
+            Ok(())
+        }
+
+        let ncns = unique("ncn", self.ncns.iter())?;
+        let operators = unique("operator", self.operators.iter().map(|o| &o.name))?;
+        let vaults = unique("vault", self.vaults.iter().map(|v| &v.name))?;
+
+        for vault in &self.vaults {
+            vault.st_mint()?;
+        }
+        for spec in &self.ncn_operators {
+            known("ncn", &ncns, &spec.ncn)?;
+            known("operator", &operators, &spec.operator)?;
+        }
+        for spec in &self.ncn_vaults {
+            known("ncn", &ncns, &spec.ncn)?;
+            known("vault", &vaults, &spec.vault)?;
+        }
+        for spec in &self.operator_vaults {
+            known("operator", &operators, &spec.operator)?;
+            known("vault", &vaults, &spec.vault)?;
+        }
+        for spec in &self.delegations {
+            known("vault", &vaults, &spec.vault)?;
+            known("operator", &operators, &spec.operator)?;
+            if !self
+                .operator_vaults
+                .iter()
+                .any(|ov| ov.operator == spec.operator && ov.vault == spec.vault)
+            {
+                anyhow::bail!(
+                    "Delegation from {} to {} needs an operator_vaults entry",
+                    spec.vault,
+                    spec.operator
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct VaultManifest {
+    pub address: String,
+    pub vrt_mint: String,
+    pub st_mint: String,
+}
+
+/// Addresses of every account created (or found) by a bootstrap run.
+///
+/// Links between accounts are keyed by `"<from>/<to>"` using the topology names.
+#[derive(Debug, Default, Serialize)]
+pub struct Manifest {
+    pub restaking_config: String,
+    pub vault_config: String,
+    pub ncns: BTreeMap<String, String>,
+    pub operators: BTreeMap<String, String>,
+    pub vaults: BTreeMap<String, VaultManifest>,
+    pub ncn_operator_states: BTreeMap<String, String>,
+    pub ncn_vault_tickets: BTreeMap<String, String>,
+    pub operator_vault_tickets: BTreeMap<String, String>,
+    pub vault_ncn_tickets: BTreeMap<String, String>,
+    pub vault_operator_delegations: BTreeMap<String, String>,
+
+    /// Whether the steps that need active tickets have run
+    pub complete: bool,
+}
+
+/// Returns true if `rpc_url` points at the local machine.
+pub fn is_local_rpc_url(rpc_url: &str) -> bool {
+    ["://localhost", "://127.0.0.1", "://0.0.0.0", "://[::1]"]
+        .iter()
+        .any(|host| rpc_url.contains(host))
+}
+
+/// Returns true if a ticket's toggle, given the slots it was last added and removed at, is
+/// warming up or active.
+fn is_warmed_up(slot_added: u64, slot_removed: u64) -> bool {
+    slot_added > slot_removed
+}
+
+/// Creates the accounts of a [`Topology`] on a local test validator.
+///
+/// Base keypairs are kept in `out_dir` so a run can be repeated: accounts that already exist are
+/// skipped and the steps after their creation run only if the account's state still needs them,
+/// which lets an interrupted run be resumed.
+pub struct Bootstrap {
+    rpc_url: String,
+    commitment: CommitmentConfig,
+    restaking_program_id: Pubkey,
+    vault_program_id: Pubkey,
+    out_dir: PathBuf,
+    restaking_handler: RestakingHandler,
+    vault_admin_handler: VaultAdminHandler,
+    vault_program_handler: VaultProgramHandler,
+}
+
+impl Bootstrap {
+    pub async fn new(
+        rpc_url: &str,
+        commitment: CommitmentConfig,
+        payer: SharedSigner,
+        restaking_program_id: Pubkey,
+        vault_program_id: Pubkey,
+        out_dir: &Path,
+    ) -> anyhow::Result<Self> {
+        fs::create_dir_all(out_dir)
+            .with_context(|| format!("Failed to create {}", out_dir.display()))?;
+
+        Ok(Self {
+            rpc_url: rpc_url.to_string(),
+            commitment,
+            restaking_program_id,
+            vault_program_id,
+            out_dir: out_dir.to_path_buf(),
+            restaking_handler: RestakingHandler::new(
+                rpc_url,
+                commitment,
+                payer.clone(),
+                restaking_program_id,
+                vault_program_id,
+            ),
+            vault_admin_handler: VaultAdminHandler::new(
+                rpc_url,
+                commitment,
+                payer,
+                restaking_program_id,
+                vault_program_id,
+            ),
+            vault_program_handler: VaultProgramHandler::new(rpc_url, commitment, vault_program_id)
+                .await?,
+        })
+    }
+
+    fn get_rpc_client(&self) -> RpcClient {
+        RpcClient::new_with_commitment(self.rpc_url.clone(), self.commitment)
+    }
+
+    async fn account_exists(&self, address: &Pubkey) -> anyhow::Result<bool> {
+        let rpc_client = self.get_rpc_client();
+        let account = rpc_client
+            .get_account_with_commitment(address, rpc_client.commitment())
+            .await
+            .with_context(|| format!("Failed to get account {address}"))?
+            .value;
+
+        Ok(account.is_some())
+    }
+
+    /// Fetches and deserializes the account at `address`, if it exists.
+    async fn get_account<T: AccountDeserialize + Copy>(
+        &self,
+        address: &Pubkey,
+    ) -> anyhow::Result<Option<T>> {
+        let rpc_client = self.get_rpc_client();
+        let Some(account) = rpc_client
+            .get_account_with_commitment(address, rpc_client.commitment())
+            .await
+            .with_context(|| format!("Failed to get account {address}"))?
+            .value
+        else {
+            return Ok(None);
+        };
+        let data = T::try_from_slice_unchecked(&account.data)
+            .map_err(|e| anyhow::anyhow!("Failed to deserialize account {address}: {e}"))?;
+
+        Ok(Some(*data))
+    }
+
+    /// Fails with a hint if either program is not deployed on the validator.
+    async fn check_programs(&self) -> anyhow::Result<()> {
+        let rpc_client = self.get_rpc_client();
+        for (program_id, so) in [
+            (self.restaking_program_id, "jito_restaking_program.so"),
+            (self.vault_program_id, "jito_vault_program.so"),
+        ] {
+            let account = rpc_client
+                .get_account_with_commitment(&program_id, rpc_client.commitment())
+                .await
+                .with_context(|| format!("Failed to get program account {program_id}"))?
+                .value;
+            if !account.is_some_and(|account| account.executable) {
+                anyhow::bail!(
+                    "Program {program_id} is not deployed, start the validator with \
+                     `--bpf-program {program_id} programs/{so}`"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the base keypair stored for `name`, generating and storing one if missing.
+    fn load_or_create_base(&self, kind: &str, name: &str) -> anyhow::Result<Keypair> {
+        let path = self.out_dir.join(format!("{kind}-{name}-base.json"));
+        if path.exists() {
+            return read_keypair_file(&path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", path.display()));
+        }
+
+        let base = Keypair::new();
+        write_keypair_file(&base, &path)
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {e}", path.display()))?;
+
+        Ok(base)
+    }
+
+    fn write_manifest(&self, manifest: &Manifest) -> anyhow::Result<PathBuf> {
+        let path = self.out_dir.join("manifest.json");
+        let data = serde_json::to_string_pretty(manifest)?;
+        fs::write(&path, data).with_context(|| format!("Failed to write {}", path.display()))?;
+
+        Ok(path)
+    }
+
+    /// Blocks until the restaking epoch after the current one has started.
+    async fn wait_for_next_epoch(&self) -> anyhow::Result<()> {
+        let rpc_client = self.get_rpc_client();
+        let epoch_length = self.restaking_handler.get_config().await?.epoch_length();
+
+        let start_epoch = rpc_client.get_slot().await? / epoch_length;
+        let target_slot = (start_epoch + 1) * epoch_length;
+        log::info!("Waiting for slot {target_slot} so warmed up tickets become active");
+
+        loop {
+            let slot = rpc_client.get_slot().await?;
+            if slot >= target_slot {
+                return Ok(());
+            }
+            log::info!("Slot {slot}, {} slots remaining", target_slot - slot);
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        }
+    }
+
+    /// Creates every account of `topology`, writing the manifest to `out_dir/manifest.json`.
+    ///
+    /// Vault NCN tickets and operator delegations need the restaking tickets to be active. When
+    /// tickets were warmed up during this run and `wait_for_activation` is false, those steps are
+    /// skipped and the run can be repeated later.
+    pub async fn run(
+        &self,
+        topology: &Topology,
+        wait_for_activation: bool,
+    ) -> anyhow::Result<(Manifest, PathBuf)> {
+        self.check_programs().await?;
+
+        let mut manifest = Manifest::default();
+        let mut warmed_up = false;
+
+        // Configs
+        let restaking_config = self.restaking_handler.config_address();
+        if !self.account_exists(&restaking_config).await? {
+            self.restaking_handler.initialize_config().await?;
+        }
+        manifest.restaking_config = restaking_config.to_string();
+
+        let vault_config = self.vault_admin_handler.config_address();
+        if !self.account_exists(&vault_config).await? {
+            self.vault_admin_handler
+                .initialize_config(topology.vault_program_fee_bps)
+                .await?;
+        }
+        manifest.vault_config = vault_config.to_string();
+
+        // NCNs
+        let mut ncns = BTreeMap::new();
+        for name in &topology.ncns {
+            let base = self.load_or_create_base("ncn", name)?;
+            let mut ncn = Ncn::find_program_address(&self.restaking_program_id, &base.pubkey()).0;
+            if !self.account_exists(&ncn).await? {
+                ncn = self
+                    .restaking_handler
+                    .initialize_ncn(Some(&base), None)
+                    .await?
+                    .0;
+            }
+            log::info!("Ncn {name}: {ncn}");
+            ncns.insert(name.clone(), ncn);
+            manifest.ncns.insert(name.clone(), ncn.to_string());
+        }
+
+        // Operators
+        let mut operators = BTreeMap::new();
+        for spec in &topology.operators {
+            let base = self.load_or_create_base("operator", &spec.name)?;
+            let mut operator =
+                Operator::find_program_address(&self.restaking_program_id, &base.pubkey()).0;
+            if !self.account_exists(&operator).await? {
+                operator = self
+                    .restaking_handler
+                    .initialize_operator(spec.fee_bps, Some(&base), None)
+                    .await?
+                    .0;
+            }
+            log::info!("Operator {}: {operator}", spec.name);
+            operators.insert(spec.name.clone(), operator);
+            manifest
+                .operators
+                .insert(spec.name.clone(), operator.to_string());
+        }
+
+        // Vaults
+        let mut vaults = BTreeMap::new();
+        for spec in &topology.vaults {
+            let base = self.load_or_create_base("vault", &spec.name)?;
+            let vault = Vault::find_program_address(&self.vault_program_id, &base.pubkey()).0;
+            if !self.account_exists(&vault).await? {
+                self.vault_admin_handler
+                    .initialize_vault(
+                        spec.st_mint()?,
+                        spec.deposit_fee_bps,
+                        spec.withdrawal_fee_bps,
+                        spec.reward_fee_bps,
+                        spec.decimals,
+                        Some(&base),
+                        None,
+                    )
+                    .await?;
+            }
+            let vault_account = self.vault_program_handler.get_vault(&vault).await?;
+            if let Some(deposit_capacity) = spec.deposit_capacity {
+                if vault_account.deposit_capacity() != deposit_capacity {
+                    self.vault_admin_handler
+                        .set_deposit_capacity(vault, deposit_capacity)
+                        .await?;
+                }
+            }
+            log::info!("Vault {}: {vault}", spec.name);
+            vaults.insert(spec.name.clone(), vault);
+            manifest.vaults.insert(
+                spec.name.clone(),
+                VaultManifest {
+                    address: vault.to_string(),
+                    vrt_mint: vault_account.vrt_mint.to_string(),
+                    st_mint: vault_account.supported_mint.to_string(),
+                },
+            );
+        }
+
+        // NCN <> Operator
+        for spec in &topology.ncn_operators {
+            let (ncn, operator) = (ncns[&spec.ncn], operators[&spec.operator]);
+            let ncn_operator_state =
+                NcnOperatorState::find_program_address(&self.restaking_program_id, &ncn, &operator)
+                    .0;
+            let state: Option<NcnOperatorState> = self.get_account(&ncn_operator_state).await?;
+            if state.is_none() {
+                self.restaking_handler
+                    .initialize_ncn_operator_state(ncn, operator)
+                    .await?;
+            }
+            if !state.is_some_and(|state| {
+                is_warmed_up(
+                    state.ncn_opt_in_state.slot_added(),
+                    state.ncn_opt_in_state.slot_removed(),
+                )
+            }) {
+                self.restaking_handler
+                    .ncn_warmup_operator(ncn, operator)
+                    .await?;
+                warmed_up = true;
+            }
+            if !state.is_some_and(|state| {
+                is_warmed_up(
+                    state.operator_opt_in_state.slot_added(),
+                    state.operator_opt_in_state.slot_removed(),
+                )
+            }) {
+                self.restaking_handler
+                    .operator_warmup_ncn(ncn, operator)
+                    .await?;
+                warmed_up = true;
+            }
+            manifest.ncn_operator_states.insert(
+                format!("{}/{}", spec.ncn, spec.operator),
+                ncn_operator_state.to_string(),
+            );
+        }
+
+        // NCN <> Vault
+        for spec in &topology.ncn_vaults {
+            let (ncn, vault) = (ncns[&spec.ncn], vaults[&spec.vault]);
+            let ncn_vault_ticket =
+                NcnVaultTicket::find_program_address(&self.restaking_program_id, &ncn, &vault).0;
+            let ticket: Option<NcnVaultTicket> = self.get_account(&ncn_vault_ticket).await?;
+            if ticket.is_none() {
+                self.restaking_handler
+                    .initialize_ncn_vault_ticket(ncn, vault)
+                    .await?;
+            }
+            if !ticket.is_some_and(|ticket| {
+                is_warmed_up(ticket.state.slot_added(), ticket.state.slot_removed())
+            }) {
+                self.restaking_handler
+                    .warmup_ncn_vault_ticket(ncn, vault)
+                    .await?;
+                warmed_up = true;
+            }
+            manifest.ncn_vault_tickets.insert(
+                format!("{}/{}", spec.ncn, spec.vault),
+                ncn_vault_ticket.to_string(),
+            );
+        }
+
+        // Operator <> Vault
+        for spec in &topology.operator_vaults {
+            let (operator, vault) = (operators[&spec.operator], vaults[&spec.vault]);
+            let operator_vault_ticket = OperatorVaultTicket::find_program_address(
+                &self.restaking_program_id,
+                &operator,
+                &vault,
+            )
+            .0;
+            let ticket: Option<OperatorVaultTicket> =
+                self.get_account(&operator_vault_ticket).await?;
+            if ticket.is_none() {
+                self.restaking_handler
+                    .initialize_operator_vault_ticket(operator, vault)
+                    .await?;
+            }
+            if !ticket.is_some_and(|ticket| {
+                is_warmed_up(ticket.state.slot_added(), ticket.state.slot_removed())
+            }) {
+                self.restaking_handler
+                    .warmup_operator_vault_ticket(operator, vault)
+                    .await?;
+                warmed_up = true;
+            }
+            manifest.operator_vault_tickets.insert(
+                format!("{}/{}", spec.operator, spec.vault),
+                operator_vault_ticket.to_string(),
+            );
+        }
+
+        if warmed_up {
+            if !wait_for_activation {
+                log::info!("Tickets were warmed up, skipping the steps that need them active");
+                let path = self.write_manifest(&manifest)?;
+                return Ok((manifest, path));
+            }
+            self.wait_for_next_epoch().await?;
+        }
+
+        // Vault <> NCN
+        for spec in &topology.ncn_vaults {
+            let (ncn, vault) = (ncns[&spec.ncn], vaults[&spec.vault]);
+            let vault_ncn_ticket =
+                VaultNcnTicket::find_program_address(&self.vault_program_id, &vault, &ncn).0;
+            let ticket: Option<VaultNcnTicket> = self.get_account(&vault_ncn_ticket).await?;
+            if ticket.is_none() {
+                self.vault_admin_handler
+                    .initialize_vault_ncn_ticket(vault, ncn)
+                    .await?;
+            }
+            if !ticket.is_some_and(|ticket| {
+                is_warmed_up(ticket.state.slot_added(), ticket.state.slot_removed())
+            }) {
+                self.vault_admin_handler
+                    .warmup_vault_ncn_ticket(vault, ncn)
+                    .await?;
+            }
+            manifest.vault_ncn_tickets.insert(
+                format!("{}/{}", spec.vault, spec.ncn),
+                vault_ncn_ticket.to_string(),
+            );
+        }
+
+        // Vault <> Operator
+        for spec in &topology.delegations {
+            let (vault, operator) = (vaults[&spec.vault], operators[&spec.operator]);
+            let vault_operator_delegation = VaultOperatorDelegation::find_program_address(
+                &self.vault_program_id,
+                &vault,
+                &operator,
+            )
+            .0;
+            let delegation: Option<VaultOperatorDelegation> =
+                self.get_account(&vault_operator_delegation).await?;
+            if delegation.is_none() {
+                self.vault_admin_handler
+                    .initialize_vault_operator_delegation(vault, operator)
+                    .await?;
+            }
+            // Only the part of the amount not staked yet is delegated, so a run does not
+            // delegate twice
+            let staked =
+                delegation.map_or(0, |delegation| delegation.delegation_state.staked_amount());
+            if spec.amount > staked {
+                self.vault_admin_handler
+                    .add_delegation(vault, operator, spec.amount - staked)
+                    .await?;
+            }
+            manifest.vault_operator_delegations.insert(
+                format!("{}/{}", spec.vault, spec.operator),
+                vault_operator_delegation.to_string(),
+            );
+        }
+
+        manifest.complete = true;
+        let path = self.write_manifest(&manifest)?;
+
+        Ok((manifest, path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topology_validate() {
+        let topology: Topology = serde_json::from_str(
+            r#"{
+                "ncns": ["ncn-0"],
+                "operators": [{ "name": "operator-0", "fee_bps": 100 }],
+                "vaults": [{ "name": "vault-0" }],
+                "ncn_operators": [{ "ncn": "ncn-0", "operator": "operator-0" }],
+                "ncn_vaults": [{ "ncn": "ncn-0", "vault": "vault-0" }],
+                "operator_vaults": [{ "operator": "operator-0", "vault": "vault-0" }],
+                "delegations": [{ "vault": "vault-0", "operator": "operator-0" }]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(topology.validate().is_ok());
+        assert_eq!(topology.vaults[0].decimals, 9);
+        assert_eq!(topology.vaults[0].st_mint().unwrap(), NATIVE_MINT);
+    }
+
+    #[test]
+    fn test_topology_validate_unknown_reference() {
+        let topology: Topology = serde_json::from_str(
+            r#"{
+                "ncns": ["ncn-0"],
+                "vaults": [{ "name": "vault-0" }],
+                "ncn_vaults": [{ "ncn": "ncn-1", "vault": "vault-0" }]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(topology.validate().is_err());
+    }
+
+    #[test]
+    fn test_is_warmed_up() {
+        // A new toggle is added and removed at its creation slot
+        assert!(!is_warmed_up(100, 100));
+        assert!(is_warmed_up(150, 100));
+        assert!(!is_warmed_up(150, 200));
+    }
+
+    #[test]
+    fn test_is_local_rpc_url() {
+        assert!(is_local_rpc_url("http://localhost:8899"));
+        assert!(is_local_rpc_url("http://127.0.0.1:8899"));
+        assert!(!is_local_rpc_url("https://api.devnet.solana.com"));
+    }
+}
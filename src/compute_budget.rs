@@ -0,0 +1,276 @@
+//! Compute-unit limits set from simulating transactions before they are sent.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use anyhow::Context;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
+use solana_sdk::{
+    address_lookup_table::AddressLookupTableAccount,
+    compute_budget::{self, ComputeBudgetInstruction},
+    hash::Hash,
+    instruction::Instruction,
+    message::{v0, VersionedMessage},
+    packet::PACKET_DATA_SIZE,
+    program_utils::limited_deserialize,
+    pubkey::Pubkey,
+    signature::Signature,
+    system_instruction::SystemInstruction,
+    system_program,
+    transaction::{Transaction, VersionedTransaction},
+};
+
+/// Largest compute-unit limit a transaction can request
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// Margin added to the simulated compute units by default, in percent
+pub const DEFAULT_COMPUTE_UNIT_MARGIN_PERCENT: u64 = 20;
+
+/// Identifies the kind of an instruction: its program and first data byte, the discriminator of
+/// the Jito programs.
+type InstructionKind = (Pubkey, Option<u8>);
+
+fn instruction_kind(ix: &Instruction) -> InstructionKind {
+    (ix.program_id, ix.data.first().copied())
+}
+
+fn is_nonce_advance(ix: &Instruction) -> bool {
+    ix.program_id == system_program::id()
+        && matches!(
+            limited_deserialize(&ix.data, PACKET_DATA_SIZE as u64),
+            Ok(SystemInstruction::AdvanceNonceAccount)
+        )
+}
+
+/// Whether `ix` only sets up the transaction: a compute-budget instruction or the advance of a
+/// durable nonce.
+fn is_setup(ix: &Instruction) -> bool {
+    ix.program_id == compute_budget::id() || is_nonce_advance(ix)
+}
+
+/// Sets compute-unit limits from simulations and keeps the compute units measured per kind of
+/// instruction, which size the crank batches.
+#[derive(Debug)]
+pub struct ComputeUnitEstimator {
+    /// Added to the simulated compute units, in percent
+    margin_percent: u64,
+
+    /// Moving average of the compute units of each kind of instruction
+    per_instruction: Mutex<HashMap<InstructionKind, u64>>,
+}
+
+impl Default for ComputeUnitEstimator {
+    fn default() -> Self {
+        Self::new(DEFAULT_COMPUTE_UNIT_MARGIN_PERCENT)
+    }
+}
+
+impl ComputeUnitEstimator {
+    pub fn new(margin_percent: u64) -> Self {
+        Self {
+            margin_percent,
+            per_instruction: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the compute-unit limit of a transaction that consumed `units_consumed`.
+    pub fn limit(&self, units_consumed: u64) -> u32 {
+        let limit = units_consumed.saturating_mul(100 + self.margin_percent) / 100;
+
+        limit.min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32
+    }
+
+    /// Records the compute units of a transaction made of `instructions`.
+    ///
+    /// Only transactions whose instructions are all of the same kind, besides the compute-budget
+    /// instructions and the nonce advance, are measured. The units are split evenly between them,
+    /// so each carries its share of the setup as it does when sent.
+    pub fn record(&self, instructions: &[Instruction], units_consumed: u64) {
+        let measured: Vec<&Instruction> = instructions.iter().filter(|ix| !is_setup(ix)).collect();
+        let Some(kind) = measured.first().map(|ix| instruction_kind(ix)) else {
+            return;
+        };
+        if measured.iter().any(|ix| instruction_kind(ix) != kind) {
+            return;
+        }
+
+        let units = units_consumed / measured.len() as u64;
+        self.per_instruction
+            .lock()
+            .expect("compute units lock")
+            .entry(kind)
+            .and_modify(|average| *average = (*average * 3 + units) / 4)
+            .or_insert(units);
+    }
+
+    /// Returns the compute units measured for instructions like `ix`.
+    pub fn per_instruction(&self, ix: &Instruction) -> Option<u64> {
+        self.per_instruction
+            .lock()
+            .expect("compute units lock")
+            .get(&instruction_kind(ix))
+            .copied()
+    }
+
+    /// Returns how many instructions like `ix`, up to `max`, fit in the largest compute-unit
+    /// limit with the margin.
+    ///
+    /// Returns 1 until such an instruction has been measured.
+    pub fn batch_size(&self, ix: &Instruction, max: usize) -> usize {
+        let Some(units) = self.per_instruction(ix) else {
+            return 1;
+        };
+        let budget = MAX_COMPUTE_UNIT_LIMIT as u64 * 100 / (100 + self.margin_percent);
+        let fit = (budget / units.max(1)) as usize;
+
+        fit.clamp(1, max.max(1))
+    }
+
+    /// Simulates `instructions` and puts a compute-unit limit of the units they consume plus the
+    /// margin in front of them, after the nonce advance that has to stay first.
+    ///
+    /// `instructions` are the ones sent, price and nonce advance included, so the limit covers
+    /// them all.
+    ///
+    /// The instructions are returned as they are when the simulation fails, so sending them
+    /// reports the error.
+    pub async fn with_compute_unit_limit(
+        &self,
+        rpc_client: &RpcClient,
+        instructions: Vec<Instruction>,
+        payer: &Pubkey,
+        lookup_table: Option<&AddressLookupTableAccount>,
+    ) -> Vec<Instruction> {
+        let units_consumed =
+            match simulate_compute_units(rpc_client, &instructions, payer, lookup_table).await {
+                Ok(units_consumed) => units_consumed,
+                Err(e) => {
+                    log::debug!("Sending without a compute-unit limit: {e:#}");
+                    return instructions;
+                }
+            };
+        self.record(&instructions, units_consumed);
+
+        let limit = self.limit(units_consumed);
+        log::debug!("Simulation consumed {units_consumed} compute units, requesting {limit}");
+        let mut limited = instructions;
+        let index = match limited.first() {
+            Some(ix) if is_nonce_advance(ix) => 1,
+            _ => 0,
+        };
+        limited.insert(
+            index,
+            ComputeBudgetInstruction::set_compute_unit_limit(limit),
+        );
+        limited
+    }
+}
+
+/// Simulates `instructions` under the largest compute-unit limit and returns the compute units
+/// they consumed.
+///
+/// The transaction is not signed, the simulation skips signature checks and uses the latest
+/// blockhash.
+pub async fn simulate_compute_units(
+    rpc_client: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    lookup_table: Option<&AddressLookupTableAccount>,
+) -> anyhow::Result<u64> {
+    let mut simulated = Vec::with_capacity(instructions.len() + 1);
+    simulated.push(ComputeBudgetInstruction::set_compute_unit_limit(
+        MAX_COMPUTE_UNIT_LIMIT,
+    ));
+    simulated.extend(
+        instructions
+            .iter()
+            .filter(|ix| ix.program_id != compute_budget::id())
+            .cloned(),
+    );
+
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        commitment: Some(rpc_client.commitment()),
+        ..RpcSimulateTransactionConfig::default()
+    };
+    let result = match lookup_table {
+        Some(lookup_table) => {
+            let message = v0::Message::try_compile(
+                payer,
+                &simulated,
+                &[lookup_table.clone()],
+                Hash::default(),
+            )
+            .context("Failed to compile v0 message")?;
+            let tx = VersionedTransaction {
+                signatures: vec![
+                    Signature::default();
+                    message.header.num_required_signatures as usize
+                ],
+                message: VersionedMessage::V0(message),
+            };
+            rpc_client
+                .simulate_transaction_with_config(&tx, config)
+                .await
+        }
+        None => {
+            let tx = Transaction::new_with_payer(&simulated, Some(payer));
+            rpc_client
+                .simulate_transaction_with_config(&tx, config)
+                .await
+        }
+    }
+    .context("Failed to simulate transaction")?
+    .value;
+
+    if let Some(err) = result.err {
+        anyhow::bail!("Simulation failed: {err}");
+    }
+    result
+        .units_consumed
+        .context("Simulation did not report the compute units consumed")
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::system_instruction;
+
+    use super::*;
+
+    #[test]
+    fn test_estimator() {
+        let estimator = ComputeUnitEstimator::new(20);
+        let program_id = Pubkey::new_unique();
+        let crank = Instruction::new_with_bytes(program_id, &[11], vec![]);
+        let close = Instruction::new_with_bytes(program_id, &[12], vec![]);
+
+        assert_eq!(estimator.limit(10_000), 12_000);
+        assert_eq!(estimator.limit(1_300_000), MAX_COMPUTE_UNIT_LIMIT);
+        assert_eq!(estimator.batch_size(&crank, 10), 1);
+
+        // Mixed transactions are not measured
+        estimator.record(&[crank.clone(), close.clone()], 100_000);
+        assert_eq!(estimator.per_instruction(&crank), None);
+
+        estimator.record(&[crank.clone(), crank.clone()], 200_000);
+        assert_eq!(estimator.per_instruction(&crank), Some(100_000));
+        // The nonce advance and the price are paid for by the cranks they come with
+        let advance =
+            system_instruction::advance_nonce_account(&Pubkey::new_unique(), &Pubkey::new_unique());
+        assert!(is_nonce_advance(&advance));
+        estimator.record(
+            &[
+                advance,
+                ComputeBudgetInstruction::set_compute_unit_price(1),
+                crank.clone(),
+            ],
+            60_000,
+        );
+        assert_eq!(estimator.per_instruction(&crank), Some(90_000));
+
+        // 1.4M / 1.2 / 90k
+        assert_eq!(estimator.batch_size(&crank, 20), 12);
+        assert_eq!(estimator.batch_size(&crank, 8), 8);
+        assert_eq!(estimator.batch_size(&close, 8), 1);
+    }
+}
@@ -0,0 +1,950 @@
+//! Local SQLite record of the actions the cranker planned and executed.
+
+use std::{
+    fmt,
+    path::Path,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use rusqlite::{params, Connection, OptionalExtension};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcTransactionConfig};
+use solana_sdk::{
+    clock::Slot, commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature,
+};
+use solana_transaction_status::UiTransactionEncoding;
+
+use crate::{
+    priority_fee::{paid_compute_unit_price, paid_priority_fee},
+    spending::SpendingBudget,
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS actions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    vault TEXT NOT NULL,
+    ncn_epoch INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    status TEXT NOT NULL,
+    planned_at INTEGER NOT NULL,
+    duration_ms INTEGER,
+    error TEXT
+);
+CREATE INDEX IF NOT EXISTS actions_vault_epoch ON actions (vault, ncn_epoch);
+
+CREATE TABLE IF NOT EXISTS transactions (
+    action_id INTEGER NOT NULL REFERENCES actions (id),
+    signature TEXT NOT NULL,
+    slot INTEGER,
+    fee INTEGER,
+    compute_units INTEGER,
+    compute_unit_price INTEGER,
+    priority_fee INTEGER,
+    cost INTEGER
+);
+CREATE INDEX IF NOT EXISTS transactions_action ON transactions (action_id);
+
+CREATE TABLE IF NOT EXISTS tracker_payers (
+    tracker TEXT PRIMARY KEY,
+    payer TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS lookup_tables (
+    vault TEXT PRIMARY KEY,
+    address TEXT NOT NULL,
+    authority TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS overhead_transactions (
+    payer TEXT NOT NULL,
+    ncn_epoch INTEGER NOT NULL,
+    signature TEXT NOT NULL,
+    slot INTEGER,
+    fee INTEGER,
+    cost INTEGER
+);
+CREATE INDEX IF NOT EXISTS overhead_transactions_epoch ON overhead_transactions (ncn_epoch);
+
+CREATE TABLE IF NOT EXISTS spending_budget (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    epoch INTEGER,
+    vault INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS orphaned_lookup_tables (
+    address TEXT PRIMARY KEY,
+    vault TEXT NOT NULL,
+    authority TEXT NOT NULL
+);
+";
+
+/// Columns added to `transactions` after its first version, created by [`CrankHistory::migrate`]
+/// in databases that lack them.
+const TRANSACTION_COLUMNS: [&str; 3] = ["compute_unit_price", "priority_fee", "cost"];
+
+/// Status of a recorded action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionStatus {
+    /// Recorded before executing, still in progress
+    Planned,
+    Succeeded,
+    Failed,
+
+    /// Was still planned when the daemon stopped
+    Interrupted,
+}
+
+impl ActionStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Planned => "planned",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+            Self::Interrupted => "interrupted",
+        }
+    }
+}
+
+impl fmt::Display for ActionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ActionStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "planned" => Ok(Self::Planned),
+            "succeeded" => Ok(Self::Succeeded),
+            "failed" => Ok(Self::Failed),
+            "interrupted" => Ok(Self::Interrupted),
+            _ => Err(anyhow::anyhow!("Unknown action status: {s}")),
+        }
+    }
+}
+
+/// A transaction sent by an action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionRecord {
+    pub signature: Signature,
+
+    /// Slot, fee and compute units are unknown when the transaction could not be fetched
+    pub slot: Option<Slot>,
+    pub fee: Option<u64>,
+    pub compute_units: Option<u64>,
+
+    /// Priority fee paid per compute unit, in micro-lamports, `None` without a priority fee
+    pub compute_unit_price: Option<u64>,
+
+    /// Part of `fee` paid for priority, in lamports
+    pub priority_fee: Option<u64>,
+
+    /// Lamports the fee payer spent: the fee and the rent it paid, minus the rent it got back
+    pub cost: Option<i64>,
+}
+
+impl TransactionRecord {
+    /// Fetches the slot, fee and compute units of a sent transaction.
+    pub async fn fetch(rpc_client: &RpcClient, signature: Signature) -> Self {
+        // Transactions can not be fetched at the processed commitment
+        let commitment = if rpc_client.commitment().is_at_least_confirmed() {
+            rpc_client.commitment()
+        } else {
+            CommitmentConfig::confirmed()
+        };
+        let transaction = rpc_client
+            .get_transaction_with_config(
+                &signature,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    commitment: Some(commitment),
+                    max_supported_transaction_version: Some(0),
+                },
+            )
+            .await;
+
+        match transaction {
+            Ok(transaction) => {
+                let tx = transaction.transaction.transaction.decode();
+                let meta = transaction.transaction.meta;
+                // The fee payer comes first
+                let cost = meta.as_ref().and_then(|meta| {
+                    Some(*meta.pre_balances.first()? as i64 - *meta.post_balances.first()? as i64)
+                });
+                Self {
+                    signature,
+                    slot: Some(transaction.slot),
+                    fee: meta.as_ref().map(|meta| meta.fee),
+                    compute_units: meta.and_then(|meta| meta.compute_units_consumed.into()),
+                    compute_unit_price: tx
+                        .as_ref()
+                        .and_then(|tx| paid_compute_unit_price(&tx.message)),
+                    priority_fee: tx.as_ref().and_then(|tx| paid_priority_fee(&tx.message)),
+                    cost,
+                }
+            }
+            Err(e) => {
+                log::warn!("Failed to get transaction {signature}: {e}");
+                Self {
+                    signature,
+                    slot: None,
+                    fee: None,
+                    compute_units: None,
+                    compute_unit_price: None,
+                    priority_fee: None,
+                    cost: None,
+                }
+            }
+        }
+    }
+}
+
+/// A recorded action with its transactions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionRecord {
+    pub id: i64,
+    pub vault: Pubkey,
+    pub ncn_epoch: u64,
+    pub action: String,
+    pub status: ActionStatus,
+
+    /// Unix timestamp in seconds
+    pub planned_at: u64,
+    pub duration_ms: Option<u64>,
+    pub error: Option<String>,
+    pub transactions: Vec<TransactionRecord>,
+}
+
+/// Lamports spent on a vault in an NCN epoch, totaled by [`CrankHistory::costs`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VaultCost {
+    pub vault: Pubkey,
+    pub ncn_epoch: u64,
+    pub transactions: u64,
+
+    /// Transaction fees, priority fees included
+    pub fees: u64,
+    pub priority_fees: u64,
+
+    /// Rent paid for the tracker and the lookup table minus rent got back, which leaves the
+    /// lookup table rent once the tracker of the epoch is closed
+    pub rent: i64,
+}
+
+impl VaultCost {
+    /// Lamports spent on fees and rent that was not got back.
+    pub fn total(&self) -> i64 {
+        self.fees as i64 + self.rent
+    }
+}
+
+/// Lamports spent in an NCN epoch on the durable nonce accounts of the payers rather than on a
+/// vault, totaled by [`CrankHistory::overhead_cost`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OverheadCost {
+    pub transactions: u64,
+    pub fees: u64,
+
+    /// Rent of the nonce accounts, which is never got back
+    pub rent: i64,
+}
+
+impl OverheadCost {
+    pub fn total(&self) -> i64 {
+        self.fees as i64 + self.rent
+    }
+}
+
+/// Filters of [`CrankHistory::query`].
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    pub vault: Option<Pubkey>,
+    pub ncn_epoch: Option<u64>,
+    pub status: Option<ActionStatus>,
+    pub limit: Option<usize>,
+}
+
+pub struct CrankHistory {
+    conn: Mutex<Connection>,
+}
+
+impl CrankHistory {
+    /// Opens the database at `path`, creating it if needed.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open history database {}", path.display()))?;
+        Self::from_connection(conn)
+    }
+
+    /// Opens a database that lives only as long as the returned value.
+    pub fn open_in_memory() -> anyhow::Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> anyhow::Result<Self> {
+        conn.execute_batch(SCHEMA)
+            .context("Failed to create history tables")?;
+        Self::migrate(&conn).context("Failed to migrate history tables")?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Adds the columns that databases created by earlier versions lack.
+    fn migrate(conn: &Connection) -> anyhow::Result<()> {
+        for column in TRANSACTION_COLUMNS {
+            let exists = conn
+                .prepare("SELECT 1 FROM pragma_table_info('transactions') WHERE name = ?1")?
+                .exists(params![column])?;
+            if !exists {
+                conn.execute(
+                    &format!("ALTER TABLE transactions ADD COLUMN {column} INTEGER"),
+                    [],
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().expect("history connection poisoned")
+    }
+
+    /// Marks the actions that were still planned as interrupted and returns them.
+    ///
+    /// Called once on startup, before this process plans anything.
+    pub fn interrupt_planned(&self) -> anyhow::Result<Vec<ActionRecord>> {
+        let planned = self.query(&HistoryFilter {
+            status: Some(ActionStatus::Planned),
+            ..HistoryFilter::default()
+        })?;
+        self.conn().execute(
+            "UPDATE actions SET status = ?1 WHERE status = ?2",
+            params![
+                ActionStatus::Interrupted.as_str(),
+                ActionStatus::Planned.as_str()
+            ],
+        )?;
+
+        Ok(planned)
+    }
+
+    /// Records an action before it is executed and returns its id.
+    pub fn plan(&self, vault: &Pubkey, ncn_epoch: u64, action: &str) -> anyhow::Result<i64> {
+        let planned_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO actions (vault, ncn_epoch, action, status, planned_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                vault.to_string(),
+                ncn_epoch,
+                action,
+                ActionStatus::Planned.as_str(),
+                planned_at
+            ],
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Records the outcome of a planned action.
+    pub fn finish(
+        &self,
+        id: i64,
+        duration: Duration,
+        transactions: &[TransactionRecord],
+        error: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let status = match error {
+            Some(_) => ActionStatus::Failed,
+            None => ActionStatus::Succeeded,
+        };
+
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE actions SET status = ?1, duration_ms = ?2, error = ?3 WHERE id = ?4",
+            params![status.as_str(), duration.as_millis() as u64, error, id],
+        )?;
+        for transaction in transactions {
+            tx.execute(
+                "INSERT INTO transactions (action_id, signature, slot, fee, compute_units,
+                 compute_unit_price, priority_fee, cost)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    id,
+                    transaction.signature.to_string(),
+                    transaction.slot,
+                    transaction.fee,
+                    transaction.compute_units,
+                    transaction.compute_unit_price,
+                    transaction.priority_fee,
+                    transaction.cost
+                ],
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Records an action that already ran, for steps taken outside of a planned action.
+    pub fn record(
+        &self,
+        vault: &Pubkey,
+        ncn_epoch: u64,
+        action: &str,
+        duration: Duration,
+        transactions: &[TransactionRecord],
+        error: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let id = self.plan(vault, ncn_epoch, action)?;
+        self.finish(id, duration, transactions, error)
+    }
+
+    /// Records the payer that initialized `tracker`, which gets the rent back when it is closed.
+    pub fn record_tracker_payer(&self, tracker: &Pubkey, payer: &Pubkey) -> anyhow::Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO tracker_payers (tracker, payer) VALUES (?1, ?2)",
+            params![tracker.to_string(), payer.to_string()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns the payer that initialized `tracker`, if it was initialized by this cranker.
+    pub fn tracker_payer(&self, tracker: &Pubkey) -> anyhow::Result<Option<Pubkey>> {
+        let payer = self
+            .conn()
+            .query_row(
+                "SELECT payer FROM tracker_payers WHERE tracker = ?1",
+                params![tracker.to_string()],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+
+        Ok(payer.map(|payer| Pubkey::from_str(&payer)).transpose()?)
+    }
+
+    /// Records the address lookup table of `vault` and the payer that is its authority.
+    pub fn record_lookup_table(
+        &self,
+        vault: &Pubkey,
+        address: &Pubkey,
+        authority: &Pubkey,
+    ) -> anyhow::Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO lookup_tables (vault, address, authority) VALUES (?1, ?2, ?3)",
+            params![
+                vault.to_string(),
+                address.to_string(),
+                authority.to_string()
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns the address lookup table of `vault` and its authority.
+    pub fn lookup_table(&self, vault: &Pubkey) -> anyhow::Result<Option<(Pubkey, Pubkey)>> {
+        let table = self
+            .conn()
+            .query_row(
+                "SELECT address, authority FROM lookup_tables WHERE vault = ?1",
+                params![vault.to_string()],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?;
+
+        match table {
+            Some((address, authority)) => Ok(Some((
+                Pubkey::from_str(&address)?,
+                Pubkey::from_str(&authority)?,
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    /// Records a lookup table `vault` no longer uses, to be deactivated and closed by `authority`.
+    pub fn record_orphaned_lookup_table(
+        &self,
+        vault: &Pubkey,
+        address: &Pubkey,
+        authority: &Pubkey,
+    ) -> anyhow::Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO orphaned_lookup_tables (address, vault, authority)
+             VALUES (?1, ?2, ?3)",
+            params![
+                address.to_string(),
+                vault.to_string(),
+                authority.to_string()
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns the lookup tables `vault` no longer uses and their authorities.
+    pub fn orphaned_lookup_tables(&self, vault: &Pubkey) -> anyhow::Result<Vec<(Pubkey, Pubkey)>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT address, authority FROM orphaned_lookup_tables
+             WHERE vault = ?1
+             ORDER BY address",
+        )?;
+        let rows = stmt.query_map(params![vault.to_string()], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut tables = Vec::new();
+        for row in rows {
+            let (address, authority) = row?;
+            tables.push((Pubkey::from_str(&address)?, Pubkey::from_str(&authority)?));
+        }
+
+        Ok(tables)
+    }
+
+    /// Forgets an orphaned lookup table once it is closed.
+    pub fn remove_orphaned_lookup_table(&self, address: &Pubkey) -> anyhow::Result<()> {
+        self.conn().execute(
+            "DELETE FROM orphaned_lookup_tables WHERE address = ?1",
+            params![address.to_string()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns the matching actions, most recent first.
+    pub fn query(&self, filter: &HistoryFilter) -> anyhow::Result<Vec<ActionRecord>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, vault, ncn_epoch, action, status, planned_at, duration_ms, error
+             FROM actions
+             WHERE (?1 IS NULL OR vault = ?1)
+               AND (?2 IS NULL OR ncn_epoch = ?2)
+               AND (?3 IS NULL OR status = ?3)
+             ORDER BY id DESC
+             LIMIT ?4",
+        )?;
+        let rows = stmt.query_map(
+            params![
+                filter.vault.map(|vault| vault.to_string()),
+                filter.ncn_epoch,
+                filter.status.map(|status| status.as_str()),
+                filter.limit.map_or(-1, |limit| limit as i64)
+            ],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, u64>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, u64>(5)?,
+                    row.get::<_, Option<u64>>(6)?,
+                    row.get::<_, Option<String>>(7)?,
+                ))
+            },
+        )?;
+
+        let mut transactions_stmt = conn.prepare(
+            "SELECT signature, slot, fee, compute_units, compute_unit_price, priority_fee, cost
+             FROM transactions WHERE action_id = ?1",
+        )?;
+
+        let mut records = Vec::new();
+        for row in rows {
+            let (id, vault, ncn_epoch, action, status, planned_at, duration_ms, error) = row?;
+            let transactions = transactions_stmt
+                .query_map(params![id], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, Option<u64>>(1)?,
+                        row.get::<_, Option<u64>>(2)?,
+                        row.get::<_, Option<u64>>(3)?,
+                        row.get::<_, Option<u64>>(4)?,
+                        row.get::<_, Option<u64>>(5)?,
+                        row.get::<_, Option<i64>>(6)?,
+                    ))
+                })?
+                .map(|row| {
+                    let (
+                        signature,
+                        slot,
+                        fee,
+                        compute_units,
+                        compute_unit_price,
+                        priority_fee,
+                        cost,
+                    ) = row?;
+                    Ok(TransactionRecord {
+                        signature: Signature::from_str(&signature)?,
+                        slot,
+                        fee,
+                        compute_units,
+                        compute_unit_price,
+                        priority_fee,
+                        cost,
+                    })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            records.push(ActionRecord {
+                id,
+                vault: Pubkey::from_str(&vault)?,
+                ncn_epoch,
+                action,
+                status: status.parse()?,
+                planned_at,
+                duration_ms,
+                error,
+                transactions,
+            });
+        }
+
+        Ok(records)
+    }
+
+    /// Returns the lamports spent on each vault in `ncn_epoch`, in the order of the vaults.
+    ///
+    /// Transactions whose cost could not be fetched count their fee only.
+    pub fn costs(&self, ncn_epoch: u64) -> anyhow::Result<Vec<VaultCost>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT actions.vault,
+                    COUNT(*),
+                    COALESCE(SUM(transactions.fee), 0),
+                    COALESCE(SUM(transactions.priority_fee), 0),
+                    COALESCE(SUM(transactions.cost - transactions.fee), 0)
+             FROM actions JOIN transactions ON transactions.action_id = actions.id
+             WHERE actions.ncn_epoch = ?1
+             GROUP BY actions.vault
+             ORDER BY actions.vault",
+        )?;
+        let rows = stmt.query_map(params![ncn_epoch], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, u64>(1)?,
+                row.get::<_, u64>(2)?,
+                row.get::<_, u64>(3)?,
+                row.get::<_, i64>(4)?,
+            ))
+        })?;
+
+        rows.map(|row| {
+            let (vault, transactions, fees, priority_fees, rent) = row?;
+            Ok(VaultCost {
+                vault: Pubkey::from_str(&vault)?,
+                ncn_epoch,
+                transactions,
+                fees,
+                priority_fees,
+                rent,
+            })
+        })
+        .collect()
+    }
+
+    /// Records a transaction `payer` sent for the cranker as a whole rather than for a vault,
+    /// creating its nonce accounts.
+    pub fn record_overhead(
+        &self,
+        payer: &Pubkey,
+        ncn_epoch: u64,
+        transaction: &TransactionRecord,
+    ) -> anyhow::Result<()> {
+        self.conn().execute(
+            "INSERT INTO overhead_transactions (payer, ncn_epoch, signature, slot, fee, cost)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                payer.to_string(),
+                ncn_epoch,
+                transaction.signature.to_string(),
+                transaction.slot,
+                transaction.fee,
+                transaction.cost
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Records the budget `run` enforces, so `status` shows what is left of it.
+    pub fn record_budget(&self, budget: &SpendingBudget) -> anyhow::Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO spending_budget (id, epoch, vault) VALUES (0, ?1, ?2)",
+            params![budget.epoch, budget.vault],
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns the budget of the last `run`, if it ran with this history.
+    pub fn budget(&self) -> anyhow::Result<Option<SpendingBudget>> {
+        let budget = self
+            .conn()
+            .query_row(
+                "SELECT epoch, vault FROM spending_budget WHERE id = 0",
+                [],
+                |row| {
+                    Ok(SpendingBudget {
+                        epoch: row.get(0)?,
+                        vault: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?;
+
+        Ok(budget)
+    }
+
+    /// Returns the lamports spent on creating nonce accounts in `ncn_epoch`.
+    pub fn overhead_cost(&self, ncn_epoch: u64) -> anyhow::Result<OverheadCost> {
+        let cost = self.conn().query_row(
+            "SELECT COUNT(*), COALESCE(SUM(fee), 0), COALESCE(SUM(cost - fee), 0)
+             FROM overhead_transactions
+             WHERE ncn_epoch = ?1",
+            params![ncn_epoch],
+            |row| {
+                Ok(OverheadCost {
+                    transactions: row.get(0)?,
+                    fees: row.get(1)?,
+                    rent: row.get(2)?,
+                })
+            },
+        )?;
+
+        Ok(cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_and_finish() {
+        let history = CrankHistory::open_in_memory().unwrap();
+        let vault = Pubkey::new_unique();
+
+        let id = history.plan(&vault, 7, "Crank").unwrap();
+        let transaction = TransactionRecord {
+            signature: Signature::new_unique(),
+            slot: Some(100),
+            fee: Some(5000),
+            compute_units: None,
+            compute_unit_price: Some(1_000),
+            priority_fee: Some(200),
+            cost: Some(5200),
+        };
+        history
+            .finish(id, Duration::from_millis(250), &[transaction.clone()], None)
+            .unwrap();
+
+        let records = history
+            .query(&HistoryFilter {
+                vault: Some(vault),
+                ..HistoryFilter::default()
+            })
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].status, ActionStatus::Succeeded);
+        assert_eq!(records[0].duration_ms, Some(250));
+        assert_eq!(records[0].transactions, vec![transaction]);
+    }
+
+    #[test]
+    fn test_costs() {
+        let history = CrankHistory::open_in_memory().unwrap();
+        let vault = Pubkey::new_unique();
+        let transaction = |fee: u64, cost: Option<i64>| TransactionRecord {
+            signature: Signature::new_unique(),
+            slot: None,
+            fee: Some(fee),
+            compute_units: None,
+            compute_unit_price: None,
+            priority_fee: Some(fee - 5000),
+            cost,
+        };
+
+        let id = history.plan(&vault, 7, "Initialize").unwrap();
+        history
+            .finish(
+                id,
+                Duration::ZERO,
+                &[transaction(6000, Some(1_006_000))],
+                None,
+            )
+            .unwrap();
+        let id = history.plan(&vault, 7, "Crank").unwrap();
+        history
+            .finish(id, Duration::ZERO, &[transaction(5000, None)], None)
+            .unwrap();
+        let id = history.plan(&Pubkey::new_unique(), 8, "Close").unwrap();
+        history
+            .finish(id, Duration::ZERO, &[transaction(5000, Some(5000))], None)
+            .unwrap();
+
+        let costs = history.costs(7).unwrap();
+        assert_eq!(
+            costs,
+            vec![VaultCost {
+                vault,
+                ncn_epoch: 7,
+                transactions: 2,
+                fees: 11_000,
+                priority_fees: 1000,
+                rent: 1_000_000,
+            }]
+        );
+        assert_eq!(costs[0].total(), 1_011_000);
+
+        // Closing gets the rent back
+        let id = history.plan(&vault, 7, "Close").unwrap();
+        history
+            .finish(
+                id,
+                Duration::ZERO,
+                &[transaction(5000, Some(-995_000))],
+                None,
+            )
+            .unwrap();
+        assert_eq!(history.costs(7).unwrap()[0].total(), 16_000);
+
+        // Nonce accounts are overhead, not spent on a vault
+        assert_eq!(history.overhead_cost(7).unwrap(), OverheadCost::default());
+        history
+            .record_overhead(
+                &Pubkey::new_unique(),
+                7,
+                &transaction(5000, Some(1_505_000)),
+            )
+            .unwrap();
+        assert_eq!(history.costs(7).unwrap().len(), 1);
+        assert_eq!(history.query(&HistoryFilter::default()).unwrap().len(), 4);
+        assert_eq!(
+            history.overhead_cost(7).unwrap(),
+            OverheadCost {
+                transactions: 1,
+                fees: 5000,
+                rent: 1_500_000,
+            }
+        );
+    }
+
+    #[test]
+    fn test_budget() {
+        let history = CrankHistory::open_in_memory().unwrap();
+        assert_eq!(history.budget().unwrap(), None);
+
+        let budget = SpendingBudget {
+            epoch: Some(1_000_000_000),
+            vault: None,
+        };
+        history.record_budget(&SpendingBudget::default()).unwrap();
+        history.record_budget(&budget).unwrap();
+        assert_eq!(history.budget().unwrap(), Some(budget));
+    }
+
+    #[test]
+    fn test_migrate() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE transactions (
+                action_id INTEGER NOT NULL,
+                signature TEXT NOT NULL,
+                slot INTEGER,
+                fee INTEGER,
+                compute_units INTEGER
+            );",
+        )
+        .unwrap();
+
+        let history = CrankHistory::from_connection(conn).unwrap();
+        let vault = Pubkey::new_unique();
+        let id = history.plan(&vault, 7, "Close").unwrap();
+        let transaction = TransactionRecord {
+            signature: Signature::new_unique(),
+            slot: None,
+            fee: None,
+            compute_units: None,
+            compute_unit_price: Some(500),
+            priority_fee: None,
+            cost: None,
+        };
+        history
+            .finish(id, Duration::ZERO, &[transaction.clone()], None)
+            .unwrap();
+        assert_eq!(
+            history.query(&HistoryFilter::default()).unwrap()[0].transactions,
+            vec![transaction]
+        );
+        CrankHistory::migrate(&history.conn()).unwrap();
+    }
+
+    #[test]
+    fn test_interrupt_planned() {
+        let history = CrankHistory::open_in_memory().unwrap();
+        let vault = Pubkey::new_unique();
+
+        history.plan(&vault, 7, "Initialize").unwrap();
+        let id = history.plan(&vault, 7, "Crank").unwrap();
+        history
+            .finish(id, Duration::ZERO, &[], Some("failed"))
+            .unwrap();
+
+        let interrupted = history.interrupt_planned().unwrap();
+        assert_eq!(interrupted.len(), 1);
+        assert_eq!(interrupted[0].action, "Initialize");
+
+        let records = history
+            .query(&HistoryFilter {
+                status: Some(ActionStatus::Interrupted),
+                ..HistoryFilter::default()
+            })
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(history.interrupt_planned().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_tracker_payer() {
+        let history = CrankHistory::open_in_memory().unwrap();
+        let tracker = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+
+        assert_eq!(history.tracker_payer(&tracker).unwrap(), None);
+        history.record_tracker_payer(&tracker, &payer).unwrap();
+        assert_eq!(history.tracker_payer(&tracker).unwrap(), Some(payer));
+
+        let vault = Pubkey::new_unique();
+        let table = Pubkey::new_unique();
+        assert_eq!(history.lookup_table(&vault).unwrap(), None);
+        history.record_lookup_table(&vault, &table, &payer).unwrap();
+        assert_eq!(history.lookup_table(&vault).unwrap(), Some((table, payer)));
+
+        assert!(history.orphaned_lookup_tables(&vault).unwrap().is_empty());
+        history
+            .record_orphaned_lookup_table(&vault, &table, &payer)
+            .unwrap();
+        assert_eq!(
+            history.orphaned_lookup_tables(&vault).unwrap(),
+            vec![(table, payer)]
+        );
+        history.remove_orphaned_lookup_table(&table).unwrap();
+        assert!(history.orphaned_lookup_tables(&vault).unwrap().is_empty());
+    }
+}
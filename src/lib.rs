@@ -1,4 +1,23 @@
+pub mod account_layout;
+pub mod account_subscriber;
+pub mod alert;
+pub mod bootstrap;
+pub mod compute_budget;
+pub mod history;
+pub mod lookup_table;
+pub mod nonce_pool;
+pub mod payer_monitor;
+pub mod payer_pool;
+pub mod priority_fee;
+pub mod program_error;
+pub mod report;
 pub mod restaking_handler;
+pub mod signer;
+pub mod spending;
+#[cfg(test)]
+mod test_server;
+pub mod transaction_export;
+pub mod vault_admin_handler;
 pub mod vault_program_handler;
 pub mod vault_state_manager;
 pub mod vault_update_state_tracker_handler;
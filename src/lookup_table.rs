@@ -0,0 +1,349 @@
+//! Address lookup tables of the vaults, which let batched cranks fit in one v0 transaction.
+
+use std::{collections::HashSet, time::Instant};
+
+use anyhow::Context;
+use jito_vault_core::vault_operator_delegation::VaultOperatorDelegation;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    address_lookup_table::{
+        instruction::{
+            close_lookup_table, create_lookup_table, deactivate_lookup_table, extend_lookup_table,
+        },
+        state::{AddressLookupTable, LOOKUP_TABLE_MAX_ADDRESSES},
+        AddressLookupTableAccount,
+    },
+    clock::Slot,
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::Signer,
+    slot_hashes,
+};
+
+use crate::{
+    history::{CrankHistory, TransactionRecord},
+    payer_pool::PayerPool,
+    signer::sign_transaction,
+};
+
+/// Addresses added per extend transaction, to stay under the transaction size limit
+const EXTEND_BATCH_SIZE: usize = 20;
+
+/// Returns the addresses the lookup table of a vault holds: the vault config, the vault, and
+/// the operator and delegation of every delegation.
+pub fn vault_addresses(
+    config: &Pubkey,
+    vault: &Pubkey,
+    delegations: &[(Pubkey, VaultOperatorDelegation)],
+) -> Vec<Pubkey> {
+    let mut addresses = vec![*config, *vault];
+    for (delegation_pubkey, delegation) in delegations {
+        addresses.push(delegation.operator);
+        addresses.push(*delegation_pubkey);
+    }
+
+    let mut seen = HashSet::new();
+    addresses.retain(|address| seen.insert(*address));
+    addresses
+}
+
+/// Returns the addresses of `wanted` missing from `existing`, as many as still fit in a table.
+fn missing_addresses(existing: &[Pubkey], wanted: &[Pubkey]) -> Vec<Pubkey> {
+    let existing: HashSet<&Pubkey> = existing.iter().collect();
+    let room = LOOKUP_TABLE_MAX_ADDRESSES.saturating_sub(existing.len());
+
+    let missing: Vec<Pubkey> = wanted
+        .iter()
+        .filter(|address| !existing.contains(address))
+        .copied()
+        .collect();
+    if missing.len() > room {
+        log::warn!(
+            "Lookup table is full, {} addresses are left out",
+            missing.len() - room
+        );
+    }
+
+    missing.into_iter().take(room).collect()
+}
+
+/// Fetches the lookup table at `address`, `None` once it is closed.
+async fn get_lookup_table(
+    rpc_client: &RpcClient,
+    address: &Pubkey,
+) -> anyhow::Result<Option<(AddressLookupTableAccount, Slot)>> {
+    let account = rpc_client
+        .get_account_with_commitment(address, rpc_client.commitment())
+        .await
+        .with_context(|| format!("Failed to get lookup table {address}"))?
+        .value;
+    let Some(account) = account else {
+        return Ok(None);
+    };
+    let table = AddressLookupTable::deserialize(&account.data)
+        .map_err(|e| anyhow::anyhow!("Invalid lookup table {address}: {e}"))?;
+
+    Ok(Some((
+        AddressLookupTableAccount {
+            key: *address,
+            addresses: table.addresses.to_vec(),
+        },
+        table.meta.deactivation_slot,
+    )))
+}
+
+/// Returns the lookup table of `vault`, holding every address of `addresses`.
+///
+/// The table recorded in `history` is extended with the missing addresses by its authority, the
+/// payer that created it. A new one is created with `payer` as its authority when there is none,
+/// it was closed, or it needs new addresses and its authority is not one of `payers`. The
+/// replaced table is then recorded as orphaned, and deactivated and closed by a later call once
+/// its authority is one of `payers` again.
+///
+/// Every transaction is recorded in `history` as spent on `vault` in `ncn_epoch`.
+pub async fn ensure_lookup_table(
+    rpc_client: &RpcClient,
+    history: &CrankHistory,
+    vault: &Pubkey,
+    ncn_epoch: u64,
+    payers: &PayerPool,
+    payer: &dyn Signer,
+    addresses: &[Pubkey],
+) -> anyhow::Result<AddressLookupTableAccount> {
+    let sender = Sender {
+        rpc_client,
+        history,
+        vault,
+        ncn_epoch,
+    };
+    if let Err(e) = retire_orphaned_lookup_tables(&sender, payers).await {
+        log::warn!("Failed to close the unused lookup tables of vault {vault}: {e:#}");
+    }
+
+    if let Some((address, table_authority)) = history.lookup_table(vault)? {
+        if let Some((mut table, _deactivation_slot)) =
+            get_lookup_table(rpc_client, &address).await?
+        {
+            let missing = missing_addresses(&table.addresses, addresses);
+            if missing.is_empty() {
+                return Ok(table);
+            }
+            match payers.get(&table_authority) {
+                Some(authority) => {
+                    extend(&sender, &mut table, authority.as_ref(), &missing).await?;
+                    return Ok(table);
+                }
+                None => {
+                    log::warn!(
+                        "Authority {table_authority} of lookup table {address} of vault {vault} \
+                         is not a payer, replacing the table"
+                    );
+                    history.record_orphaned_lookup_table(vault, &address, &table_authority)?;
+                }
+            }
+        }
+    }
+
+    let authority = payer.pubkey();
+    let recent_slot = rpc_client
+        .get_slot_with_commitment(CommitmentConfig::finalized())
+        .await
+        .context("Failed to get slot")?;
+    let (ix, address) = create_lookup_table(authority, authority, recent_slot);
+    sender
+        .send("CreateLookupTable", &[ix], payer)
+        .await
+        .with_context(|| format!("Failed to create lookup table of vault {vault}"))?;
+    history.record_lookup_table(vault, &address, &authority)?;
+    log::info!("Created lookup table {address} of vault {vault}");
+
+    let mut table = AddressLookupTableAccount {
+        key: address,
+        addresses: Vec::new(),
+    };
+    let missing = missing_addresses(&table.addresses, addresses);
+    extend(&sender, &mut table, payer, &missing).await?;
+
+    Ok(table)
+}
+
+/// Adds `missing` to `table`, signed and paid by its `authority`.
+async fn extend(
+    sender: &Sender<'_>,
+    table: &mut AddressLookupTableAccount,
+    authority: &dyn Signer,
+    missing: &[Pubkey],
+) -> anyhow::Result<()> {
+    let authority_pubkey = authority.pubkey();
+    for batch in missing.chunks(EXTEND_BATCH_SIZE) {
+        let ix = extend_lookup_table(
+            table.key,
+            authority_pubkey,
+            Some(authority_pubkey),
+            batch.to_vec(),
+        );
+        sender
+            .send("ExtendLookupTable", &[ix], authority)
+            .await
+            .with_context(|| format!("Failed to extend lookup table {}", table.key))?;
+        table.addresses.extend_from_slice(batch);
+        log::info!(
+            "Extended lookup table {} of vault {} with {} addresses",
+            table.key,
+            sender.vault,
+            batch.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Deactivates the orphaned lookup tables of `vault` whose authority is one of `payers`, and
+/// closes them once the deactivation slot has left the slot hashes, returning the rent to the
+/// authority.
+async fn retire_orphaned_lookup_tables(
+    sender: &Sender<'_>,
+    payers: &PayerPool,
+) -> anyhow::Result<()> {
+    let Sender {
+        rpc_client,
+        history,
+        vault,
+        ..
+    } = *sender;
+    let orphans = history.orphaned_lookup_tables(vault)?;
+    if orphans.is_empty() {
+        return Ok(());
+    }
+    let slot = rpc_client.get_slot().await.context("Failed to get slot")?;
+
+    for (address, authority) in orphans {
+        let Some(signer) = payers.get(&authority) else {
+            log::debug!("Lookup table {address} waits for its authority {authority} to close it");
+            continue;
+        };
+        let Some((_table, deactivation_slot)) = get_lookup_table(rpc_client, &address).await?
+        else {
+            history.remove_orphaned_lookup_table(&address)?;
+            continue;
+        };
+
+        if deactivation_slot == Slot::MAX {
+            sender
+                .send(
+                    "DeactivateLookupTable",
+                    &[deactivate_lookup_table(address, authority)],
+                    signer.as_ref(),
+                )
+                .await
+                .with_context(|| format!("Failed to deactivate lookup table {address}"))?;
+            log::info!("Deactivated lookup table {address} no longer used by vault {vault}");
+        } else if slot > deactivation_slot + slot_hashes::MAX_ENTRIES as u64 {
+            sender
+                .send(
+                    "CloseLookupTable",
+                    &[close_lookup_table(address, authority, authority)],
+                    signer.as_ref(),
+                )
+                .await
+                .with_context(|| format!("Failed to close lookup table {address}"))?;
+            history.remove_orphaned_lookup_table(&address)?;
+            log::info!("Closed lookup table {address} no longer used by vault {vault}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends the lookup table transactions of a vault and records them in the history as spent on
+/// the vault.
+#[derive(Clone, Copy)]
+struct Sender<'a> {
+    rpc_client: &'a RpcClient,
+    history: &'a CrankHistory,
+    vault: &'a Pubkey,
+    ncn_epoch: u64,
+}
+
+impl Sender<'_> {
+    async fn send(
+        &self,
+        action: &str,
+        instructions: &[Instruction],
+        payer: &dyn Signer,
+    ) -> anyhow::Result<()> {
+        let started = Instant::now();
+        let result = self.send_transaction(instructions, payer).await;
+
+        let transactions = match &result {
+            Ok(signature) => vec![TransactionRecord::fetch(self.rpc_client, *signature).await],
+            Err(_) => Vec::new(),
+        };
+        let error = result.as_ref().err().map(|e| format!("{e:#}"));
+        if let Err(e) = self.history.record(
+            self.vault,
+            self.ncn_epoch,
+            action,
+            started.elapsed(),
+            &transactions,
+            error.as_deref(),
+        ) {
+            log::error!("Failed to record {action} of vault {}: {e:?}", self.vault);
+        }
+
+        result.map(|_signature| ())
+    }
+
+    async fn send_transaction(
+        &self,
+        instructions: &[Instruction],
+        payer: &dyn Signer,
+    ) -> anyhow::Result<Signature> {
+        let blockhash = self
+            .rpc_client
+            .get_latest_blockhash()
+            .await
+            .context("Failed to get latest blockhash")?;
+        let tx = sign_transaction(instructions, payer, &[], blockhash)
+            .context("Failed to sign transaction")?;
+
+        Ok(self.rpc_client.send_and_confirm_transaction(&tx).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vault_addresses() {
+        let config = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let operator = Pubkey::new_unique();
+        let delegations: Vec<(Pubkey, VaultOperatorDelegation)> = (0..2)
+            .map(|index| {
+                (
+                    Pubkey::new_unique(),
+                    VaultOperatorDelegation::new(vault, operator, index, 0, 0),
+                )
+            })
+            .collect();
+
+        let addresses = vault_addresses(&config, &vault, &delegations);
+        assert_eq!(
+            addresses,
+            vec![config, vault, operator, delegations[0].0, delegations[1].0]
+        );
+
+        assert_eq!(
+            missing_addresses(&addresses[..2], &addresses),
+            addresses[2..].to_vec()
+        );
+        let full: Vec<Pubkey> = (0..LOOKUP_TABLE_MAX_ADDRESSES - 1)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        assert_eq!(missing_addresses(&full, &addresses), vec![config]);
+    }
+}
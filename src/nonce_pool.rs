@@ -0,0 +1,265 @@
+//! Durable nonce accounts that keep crank transactions valid after their blockhash expires.
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context;
+use solana_client::{nonblocking::rpc_client::RpcClient, nonce_utils};
+use solana_sdk::{
+    hash::Hash, instruction::Instruction, nonce::State, pubkey::Pubkey, signer::Signer,
+    system_instruction, system_program,
+};
+
+use crate::{
+    history::{CrankHistory, TransactionRecord},
+    signer::sign_transaction,
+    transaction_export::TransactionExporter,
+};
+
+/// Nonce accounts created per transaction, each takes two instructions
+const CREATE_BATCH_SIZE: usize = 4;
+
+/// Durable nonce accounts of every payer.
+///
+/// The accounts are derived from their payer, which is also their authority, with a seed, so
+/// they are found again after a restart and no keypair has to be kept for them.
+pub struct NoncePool {
+    /// Nonce accounts per payer
+    size: usize,
+
+    /// Nonce accounts leased out
+    in_use: Mutex<HashSet<Pubkey>>,
+}
+
+impl NoncePool {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            in_use: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn seed(index: usize) -> String {
+        format!("chrono-crank-nonce-{index}")
+    }
+
+    /// Returns the nonce accounts of `authority`.
+    pub fn addresses(&self, authority: &Pubkey) -> Vec<Pubkey> {
+        (0..self.size)
+            .map(|index| {
+                Pubkey::create_with_seed(authority, &Self::seed(index), &system_program::id())
+                    .expect("nonce seed is short enough")
+            })
+            .collect()
+    }
+
+    /// Creates the nonce accounts of `payer` that do not exist yet, or exports the transactions
+    /// creating them.
+    ///
+    /// Sent transactions are recorded in `history` as overhead of `ncn_epoch`, apart from the
+    /// actions of the vaults.
+    /// Returns the number of accounts created.
+    pub async fn create_missing(
+        &self,
+        rpc_client: &RpcClient,
+        history: &CrankHistory,
+        ncn_epoch: u64,
+        payer: &dyn Signer,
+        exporter: Option<&TransactionExporter>,
+    ) -> anyhow::Result<usize> {
+        let authority = payer.pubkey();
+        let addresses = self.addresses(&authority);
+        let accounts = rpc_client
+            .get_multiple_accounts(&addresses)
+            .await
+            .context("Failed to get nonce accounts")?;
+        let rent = rpc_client
+            .get_minimum_balance_for_rent_exemption(State::size())
+            .await
+            .context("Failed to get nonce account rent")?;
+
+        let missing: Vec<(usize, Pubkey)> = addresses
+            .into_iter()
+            .zip(accounts)
+            .enumerate()
+            .filter(|(_index, (_address, account))| account.is_none())
+            .map(|(index, (address, _account))| (index, address))
+            .collect();
+
+        for batch in missing.chunks(CREATE_BATCH_SIZE) {
+            let instructions: Vec<Instruction> = batch
+                .iter()
+                .flat_map(|(index, address)| {
+                    system_instruction::create_nonce_account_with_seed(
+                        &authority,
+                        address,
+                        &authority,
+                        &Self::seed(*index),
+                        &authority,
+                        rent,
+                    )
+                })
+                .collect();
+            let blockhash = rpc_client
+                .get_latest_blockhash()
+                .await
+                .context("Failed to get latest blockhash")?;
+
+            if let Some(exporter) = exporter {
+                exporter.export(&instructions, &authority, &[], blockhash)?;
+                continue;
+            }
+
+            let tx = sign_transaction(&instructions, payer, &[], blockhash)
+                .context("Failed to sign transaction")?;
+            let sig = rpc_client
+                .send_and_confirm_transaction(&tx)
+                .await
+                .with_context(|| format!("Failed to create nonce accounts of {authority}"))?;
+            let transaction = TransactionRecord::fetch(rpc_client, sig).await;
+            if let Err(e) = history.record_overhead(&authority, ncn_epoch, &transaction) {
+                log::error!("Failed to record the nonce accounts of {authority}: {e:?}");
+            }
+            log::info!(
+                "Created {} nonce accounts of {authority}: {sig}",
+                batch.len()
+            );
+        }
+
+        Ok(missing.len())
+    }
+
+    /// Leases a free nonce account of `authority` with its current nonce.
+    ///
+    /// Returns `None` when every nonce account of `authority` is leased out.
+    pub async fn acquire(
+        self: &Arc<Self>,
+        rpc_client: &RpcClient,
+        authority: &Pubkey,
+    ) -> anyhow::Result<Option<NonceLease>> {
+        let address = {
+            let mut in_use = self.in_use.lock().expect("nonce pool lock");
+            let Some(address) = self
+                .addresses(authority)
+                .into_iter()
+                .find(|address| !in_use.contains(address))
+            else {
+                return Ok(None);
+            };
+            in_use.insert(address);
+            address
+        };
+        // Releases the account if the nonce can not be read
+        let mut lease = NonceLease {
+            pool: self.clone(),
+            address,
+            authority: *authority,
+            nonce: Hash::default(),
+            kept: false,
+        };
+
+        let account = nonce_utils::nonblocking::get_account_with_commitment(
+            rpc_client,
+            &address,
+            rpc_client.commitment(),
+        )
+        .await
+        .with_context(|| format!("Failed to get nonce account {address} of {authority}"))?;
+        let data = nonce_utils::nonblocking::data_from_account(&account)
+            .with_context(|| format!("Invalid nonce account {address}"))?;
+        if data.authority != *authority {
+            anyhow::bail!(
+                "Nonce account {address} belongs to {}, expected {authority}",
+                data.authority
+            );
+        }
+        lease.nonce = data.blockhash();
+
+        Ok(Some(lease))
+    }
+
+    fn release(&self, address: &Pubkey) {
+        self.in_use.lock().expect("nonce pool lock").remove(address);
+    }
+}
+
+/// A nonce account leased from a [`NoncePool`], returned to it when dropped.
+pub struct NonceLease {
+    pool: Arc<NoncePool>,
+    address: Pubkey,
+    authority: Pubkey,
+    nonce: Hash,
+    kept: bool,
+}
+
+impl NonceLease {
+    pub fn address(&self) -> Pubkey {
+        self.address
+    }
+
+    /// Current nonce, used as the blockhash of the transaction.
+    pub fn nonce(&self) -> Hash {
+        self.nonce
+    }
+
+    /// Instruction advancing the nonce, which must come first in the transaction.
+    pub fn advance_instruction(&self) -> Instruction {
+        system_instruction::advance_nonce_account(&self.address, &self.authority)
+    }
+
+    /// Keeps the account leased until the process exits.
+    ///
+    /// An exported transaction only advances the nonce when it is submitted, so the account can
+    /// not be used for another transaction until then.
+    pub fn keep(mut self) {
+        self.kept = true;
+    }
+}
+
+impl Drop for NonceLease {
+    fn drop(&mut self) {
+        if !self.kept {
+            self.pool.release(&self.address);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lease() {
+        let pool = Arc::new(NoncePool::new(2));
+        let authority = Pubkey::new_unique();
+        let addresses = pool.addresses(&authority);
+        assert_eq!(addresses.len(), 2);
+        assert_eq!(addresses, pool.addresses(&authority));
+        assert_ne!(addresses, pool.addresses(&Pubkey::new_unique()));
+
+        let lease = |address: Pubkey| {
+            pool.in_use.lock().unwrap().insert(address);
+            NonceLease {
+                pool: pool.clone(),
+                address,
+                authority,
+                nonce: Hash::new_unique(),
+                kept: false,
+            }
+        };
+
+        drop(lease(addresses[0]));
+        assert!(pool.in_use.lock().unwrap().is_empty());
+
+        lease(addresses[1]).keep();
+        assert!(pool.in_use.lock().unwrap().contains(&addresses[1]));
+
+        let ix = lease(addresses[0]).advance_instruction();
+        assert_eq!(ix.program_id, system_program::id());
+        assert_eq!(ix.accounts[0].pubkey, addresses[0]);
+        assert_eq!(ix.accounts[2].pubkey, authority);
+        assert!(ix.accounts[2].is_signer);
+    }
+}
@@ -0,0 +1,79 @@
+//! Checks whether the payer can afford the next crank cycle.
+
+use crate::vault_state_manager::VaultAction;
+
+/// Base fee of a transaction with a single signature
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+/// Balance of the payer relative to its thresholds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayerStatus {
+    Healthy,
+
+    /// Below the warning threshold once the pending work is paid for
+    Low,
+
+    /// Below the floor, initializations are paused
+    BelowFloor,
+}
+
+/// Payer balance thresholds, in lamports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayerThresholds {
+    pub warn: u64,
+    pub floor: u64,
+}
+
+impl PayerThresholds {
+    pub fn status(&self, balance: u64, estimated_cost: u64) -> PayerStatus {
+        if balance < self.floor {
+            PayerStatus::BelowFloor
+        } else if balance.saturating_sub(estimated_cost) < self.warn {
+            PayerStatus::Low
+        } else {
+            PayerStatus::Healthy
+        }
+    }
+}
+
+/// Estimates the lamports spent on `actions`, given with the operator count of their vault.
+///
+/// Initializing a tracker pays `tracker_rent`, which closing it gives back, and every crank is one
+/// transaction per operator.
+pub fn estimate_cost(actions: &[(VaultAction, u64)], tracker_rent: u64) -> u64 {
+    actions
+        .iter()
+        .map(|(action, operator_count)| match action {
+            VaultAction::UpToDate | VaultAction::Skip(_) => 0,
+            VaultAction::Initialize => tracker_rent + LAMPORTS_PER_SIGNATURE,
+            VaultAction::Crank => operator_count * LAMPORTS_PER_SIGNATURE,
+            VaultAction::Close => LAMPORTS_PER_SIGNATURE,
+            VaultAction::CloseStale => tracker_rent + 2 * LAMPORTS_PER_SIGNATURE,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payer_status() {
+        let thresholds = PayerThresholds {
+            warn: 1_000_000,
+            floor: 100_000,
+        };
+        let actions = [
+            (VaultAction::Initialize, 3),
+            (VaultAction::Crank, 3),
+            (VaultAction::Close, 3),
+            (VaultAction::UpToDate, 3),
+        ];
+        let cost = estimate_cost(&actions, 2_000_000);
+        assert_eq!(cost, 2_000_000 + 5 * LAMPORTS_PER_SIGNATURE);
+
+        assert_eq!(thresholds.status(10_000_000, cost), PayerStatus::Healthy);
+        assert_eq!(thresholds.status(2_500_000, cost), PayerStatus::Low);
+        assert_eq!(thresholds.status(50_000, cost), PayerStatus::BelowFloor);
+    }
+}
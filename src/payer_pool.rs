@@ -0,0 +1,139 @@
+//! Payers the cranker spreads its transactions over.
+
+use std::{collections::HashMap, str::FromStr};
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::signer::SharedSigner;
+
+/// Assignment of a vault to a payer, parsed from `VAULT=PAYER`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VaultPayer {
+    pub vault: Pubkey,
+    pub payer: Pubkey,
+}
+
+impl FromStr for VaultPayer {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (vault, payer) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Expected VAULT=PAYER, got {s}"))?;
+
+        Ok(Self {
+            vault: Pubkey::from_str(vault.trim())?,
+            payer: Pubkey::from_str(payer.trim())?,
+        })
+    }
+}
+
+/// Hands out a payer per vault, from the per-vault assignments or the vault address.
+pub struct PayerPool {
+    payers: Vec<SharedSigner>,
+
+    /// Index in `payers` of the vaults assigned explicitly
+    vault_payers: HashMap<Pubkey, usize>,
+}
+
+impl PayerPool {
+    pub fn new(payers: Vec<SharedSigner>, vault_payers: &[VaultPayer]) -> anyhow::Result<Self> {
+        if payers.is_empty() {
+            anyhow::bail!("At least one payer is required");
+        }
+
+        let vault_payers = vault_payers
+            .iter()
+            .map(|assignment| {
+                payers
+                    .iter()
+                    .position(|payer| payer.pubkey() == assignment.payer)
+                    .map(|index| (assignment.vault, index))
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Vault {} is assigned to {}, which is not one of the payers",
+                            assignment.vault,
+                            assignment.payer
+                        )
+                    })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            payers,
+            vault_payers,
+        })
+    }
+
+    /// Payer of admin operations, the first one given.
+    pub fn primary(&self) -> &SharedSigner {
+        &self.payers[0]
+    }
+
+    pub fn payers(&self) -> &[SharedSigner] {
+        &self.payers
+    }
+
+    pub fn get(&self, pubkey: &Pubkey) -> Option<&SharedSigner> {
+        self.payers.iter().find(|payer| payer.pubkey() == *pubkey)
+    }
+
+    /// Returns the payer assigned to `vault`, or one picked from its address.
+    ///
+    /// The pick only changes with the payers, so every step of a vault in an epoch, and across
+    /// restarts, goes through the same payer and its nonce accounts.
+    pub fn assign(&self, vault: &Pubkey) -> SharedSigner {
+        let index = match self.vault_payers.get(vault) {
+            Some(index) => *index,
+            None => {
+                let bytes = vault.to_bytes();
+                let hash = u64::from_le_bytes(bytes[..8].try_into().expect("8 bytes"));
+                (hash % self.payers.len() as u64) as usize
+            }
+        };
+
+        self.payers[index].clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use solana_sdk::signature::Keypair;
+
+    use super::*;
+
+    #[test]
+    fn test_assign() {
+        let payers: Vec<SharedSigner> = vec![
+            Arc::new(Keypair::new()),
+            Arc::new(Keypair::new()),
+            Arc::new(Keypair::new()),
+        ];
+        let pubkeys: Vec<Pubkey> = payers.iter().map(|payer| payer.pubkey()).collect();
+        let assigned_vault = Pubkey::new_unique();
+        let pool = PayerPool::new(
+            payers,
+            &[VaultPayer {
+                vault: assigned_vault,
+                payer: pubkeys[2],
+            }],
+        )
+        .unwrap();
+
+        let vault = Pubkey::new_from_array([2; 32]);
+        assert_eq!(pool.assign(&vault).pubkey(), pubkeys[1]);
+        assert_eq!(pool.assign(&vault).pubkey(), pubkeys[1]);
+        assert_eq!(pool.assign(&assigned_vault).pubkey(), pubkeys[2]);
+        assert_eq!(
+            pool.assign(&Pubkey::new_from_array([3; 32])).pubkey(),
+            pubkeys[0]
+        );
+
+        let unknown = format!("{}={}", Pubkey::new_unique(), Pubkey::new_unique());
+        assert!(
+            PayerPool::new(vec![Arc::new(Keypair::new())], &[unknown.parse().unwrap()]).is_err()
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Priority fees that grow as the NCN epoch goes on and as sends fail, so a vault late for its
+//! update gets through.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use solana_sdk::{
+    borsh1::try_from_slice_unchecked,
+    clock::Slot,
+    compute_budget::{self, ComputeBudgetInstruction},
+    message::VersionedMessage,
+    pubkey::Pubkey,
+};
+
+use crate::compute_budget::MAX_COMPUTE_UNIT_LIMIT;
+
+/// Bounds and growth of the compute-unit price, in micro-lamports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriorityFeeConfig {
+    /// Price at the NCN epoch boundary
+    pub base: u64,
+
+    /// Ceiling of the price
+    pub max: u64,
+
+    /// Share of the range from `base` to `max` added per consecutive send failure of a vault, in
+    /// percent
+    pub failure_step_percent: u64,
+}
+
+/// Picks the compute-unit price of each vault's transactions.
+///
+/// The price goes from `base` at the NCN epoch boundary to `max` at its end, and every
+/// consecutive send failure of the vault adds `failure_step_percent` of that range, up to `max`.
+#[derive(Debug)]
+pub struct PriorityFeeEscalator {
+    config: PriorityFeeConfig,
+
+    /// Slots per NCN epoch
+    epoch_length: u64,
+
+    /// Consecutive send failures per vault, cleared by a confirmed transaction
+    failures: Mutex<HashMap<Pubkey, u64>>,
+}
+
+impl PriorityFeeEscalator {
+    pub fn new(config: PriorityFeeConfig, epoch_length: u64) -> Self {
+        Self {
+            config,
+            epoch_length: epoch_length.max(1),
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the compute-unit price of a transaction of `vault` sent at `slot`.
+    pub fn compute_unit_price(&self, vault: &Pubkey, slot: Slot) -> u64 {
+        let PriorityFeeConfig {
+            base,
+            max,
+            failure_step_percent,
+        } = self.config;
+        if max <= base {
+            return max;
+        }
+
+        let elapsed_percent = (slot % self.epoch_length) * 100 / self.epoch_length;
+        let failure_percent = self.failures(vault).saturating_mul(failure_step_percent);
+        let percent = elapsed_percent.saturating_add(failure_percent).min(100);
+
+        base + ((max - base) as u128 * percent as u128 / 100) as u64
+    }
+
+    pub fn failures(&self, vault: &Pubkey) -> u64 {
+        self.failures
+            .lock()
+            .expect("priority fee lock")
+            .get(vault)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn record_failure(&self, vault: &Pubkey) {
+        *self
+            .failures
+            .lock()
+            .expect("priority fee lock")
+            .entry(*vault)
+            .or_default() += 1;
+    }
+
+    pub fn record_success(&self, vault: &Pubkey) {
+        self.failures
+            .lock()
+            .expect("priority fee lock")
+            .remove(vault);
+    }
+}
+
+/// Compute-unit limit per instruction of a transaction that does not set one
+const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u64 = 200_000;
+
+/// Returns the compute-budget instructions of a transaction, and how many other instructions it
+/// has.
+fn compute_budget_instructions(message: &VersionedMessage) -> (Vec<ComputeBudgetInstruction>, u64) {
+    let account_keys = message.static_account_keys();
+
+    let mut budget = Vec::new();
+    let mut others = 0;
+    for ix in message.instructions() {
+        if account_keys.get(ix.program_id_index as usize) != Some(&compute_budget::id()) {
+            others += 1;
+        } else if let Ok(ix) = try_from_slice_unchecked(&ix.data) {
+            budget.push(ix);
+        }
+    }
+
+    (budget, others)
+}
+
+/// Returns the compute-unit price set by a transaction, in micro-lamports.
+pub fn paid_compute_unit_price(message: &VersionedMessage) -> Option<u64> {
+    compute_budget_instructions(message)
+        .0
+        .into_iter()
+        .find_map(|ix| match ix {
+            ComputeBudgetInstruction::SetComputeUnitPrice(price) => Some(price),
+            _ => None,
+        })
+}
+
+/// Returns the priority fee of a transaction in lamports: its compute-unit price times its
+/// compute-unit limit, rounded up.
+pub fn paid_priority_fee(message: &VersionedMessage) -> Option<u64> {
+    let (budget, others) = compute_budget_instructions(message);
+
+    let price = budget.iter().find_map(|ix| match ix {
+        ComputeBudgetInstruction::SetComputeUnitPrice(price) => Some(*price),
+        _ => None,
+    })?;
+    let limit = budget
+        .iter()
+        .find_map(|ix| match ix {
+            ComputeBudgetInstruction::SetComputeUnitLimit(limit) => Some(*limit as u64),
+            _ => None,
+        })
+        .unwrap_or(others * DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT)
+        .min(MAX_COMPUTE_UNIT_LIMIT as u64);
+
+    Some(((price as u128 * limit as u128 + 999_999) / 1_000_000) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{hash::Hash, message::Message};
+
+    use super::*;
+
+    #[test]
+    fn test_compute_unit_price() {
+        let fees = PriorityFeeEscalator::new(
+            PriorityFeeConfig {
+                base: 1_000,
+                max: 11_000,
+                failure_step_percent: 25,
+            },
+            100,
+        );
+        let vault = Pubkey::new_unique();
+
+        assert_eq!(fees.compute_unit_price(&vault, 300), 1_000);
+        assert_eq!(fees.compute_unit_price(&vault, 350), 6_000);
+        assert_eq!(fees.compute_unit_price(&vault, 399), 10_900);
+
+        fees.record_failure(&vault);
+        fees.record_failure(&vault);
+        assert_eq!(fees.compute_unit_price(&vault, 300), 6_000);
+        assert_eq!(fees.compute_unit_price(&vault, 350), 11_000);
+        assert_eq!(fees.compute_unit_price(&Pubkey::new_unique(), 300), 1_000);
+
+        fees.record_success(&vault);
+        assert_eq!(fees.failures(&vault), 0);
+        assert_eq!(fees.compute_unit_price(&vault, 300), 1_000);
+
+        let payer = Pubkey::new_unique();
+        let message = Message::new_with_blockhash(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(200_000),
+                ComputeBudgetInstruction::set_compute_unit_price(6_000),
+            ],
+            Some(&payer),
+            &Hash::new_unique(),
+        );
+        let message = VersionedMessage::Legacy(message);
+        assert_eq!(paid_compute_unit_price(&message), Some(6_000));
+        assert_eq!(paid_priority_fee(&message), Some(1_200));
+    }
+}
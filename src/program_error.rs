@@ -0,0 +1,265 @@
+//! Decodes failed transactions into the named Jito vault and restaking program errors.
+
+use std::fmt;
+
+use jito_restaking_client::errors::JitoRestakingError;
+use jito_vault_client::errors::JitoVaultError;
+use num_traits::FromPrimitive;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_request::{RpcError, RpcResponseErrorData},
+};
+use solana_sdk::{
+    instruction::InstructionError, signature::Signature, transaction::TransactionError,
+};
+
+/// Jito program a transaction was sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JitoProgram {
+    Vault,
+    Restaking,
+}
+
+impl JitoProgram {
+    /// Returns the name of the program error with `code`.
+    fn error_name(&self, code: u32) -> Option<String> {
+        match self {
+            Self::Vault => JitoVaultError::from_u32(code).map(|e| format!("{e:?}")),
+            Self::Restaking => JitoRestakingError::from_u32(code).map(|e| format!("{e:?}")),
+        }
+    }
+}
+
+/// Failed instruction of a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstructionFailure {
+    /// Index of the instruction in the transaction
+    pub index: u8,
+
+    /// Instruction name from the program logs, only available when preflight fails
+    pub instruction: Option<String>,
+
+    /// Program error name, or the runtime error for errors that are not custom
+    pub error: String,
+
+    pub hint: Option<&'static str>,
+
+    /// The instruction failed because what it does has already been done
+    pub benign: bool,
+}
+
+impl InstructionFailure {
+    /// Decodes the failed instruction of `error`, if the transaction got to execute.
+    pub fn from_client_error(program: JitoProgram, error: &ClientError) -> Option<Self> {
+        let TransactionError::InstructionError(index, instruction_error) =
+            error.get_transaction_error()?
+        else {
+            return None;
+        };
+
+        let logs = match error.kind() {
+            ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+                ..
+            }) => result.logs.as_deref(),
+            _ => None,
+        };
+
+        Some(Self::new(
+            program,
+            index,
+            &instruction_error,
+            logs.and_then(|logs| instruction_name(logs, index)),
+        ))
+    }
+
+    fn new(
+        program: JitoProgram,
+        index: u8,
+        instruction_error: &InstructionError,
+        instruction: Option<String>,
+    ) -> Self {
+        let error = match instruction_error {
+            InstructionError::Custom(code) => program
+                .error_name(*code)
+                .unwrap_or_else(|| format!("Custom({code})")),
+            e => format!("{e:?}"),
+        };
+        let (hint, benign) = classify(instruction.as_deref().unwrap_or_default(), &error);
+
+        Self {
+            index,
+            instruction,
+            error,
+            hint,
+            benign,
+        }
+    }
+}
+
+impl fmt::Display for InstructionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.instruction {
+            Some(instruction) => write!(f, "{instruction} (instruction {})", self.index)?,
+            None => write!(f, "Instruction {}", self.index)?,
+        }
+        write!(f, " failed with {}", self.error)?;
+        if let Some(hint) = self.hint {
+            write!(f, ": {hint}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns a hint for `error` and whether it is benign.
+fn classify(instruction: &str, error: &str) -> (Option<&'static str>, bool) {
+    match (instruction, error) {
+        // The account to create is already owned by the program
+        (
+            "InitializeVaultUpdateStateTracker",
+            "InvalidAccountOwner" | "AccountAlreadyInitialized",
+        ) => (Some("the tracker already exists"), true),
+        ("InitializeVaultUpdateStateTracker", "VaultIsUpdated") => {
+            (Some("the vault is already updated for this epoch"), true)
+        }
+        ("CloseVaultUpdateStateTracker", "InvalidAccountOwner") => {
+            (Some("the tracker is already closed"), true)
+        }
+        (_, "InvalidAccountOwner" | "AccountAlreadyInitialized")
+            if instruction.starts_with("Initialize") =>
+        {
+            (Some("the account already exists"), false)
+        }
+        (_, "VaultUpdateIncorrectIndex") => (
+            Some(
+                "delegations are cranked in index order starting at ncn_epoch % operator_count, \
+                 resume after the tracker's last_updated_index",
+            ),
+            false,
+        ),
+        (_, "VaultUpdateStateNotFinishedUpdating") => (
+            Some("crank every delegation before closing the tracker"),
+            false,
+        ),
+        (_, "VaultNeedsUpdate") => (
+            Some("the vault has to be updated for the current epoch first"),
+            false,
+        ),
+        (_, "InvalidSeeds") => (
+            Some("an address does not match its seeds, the tracker may belong to another epoch"),
+            false,
+        ),
+        _ => (None, false),
+    }
+}
+
+/// Returns the name the program logged for the top-level instruction at `index`.
+fn instruction_name(logs: &[String], index: u8) -> Option<String> {
+    let mut top_level_index = None;
+    let mut depth = 0;
+    for log in logs {
+        if let Some(rest) = log.strip_prefix("Program ") {
+            if let Some((_program, invoke)) = rest.split_once(" invoke [") {
+                depth = invoke.trim_end_matches(']').parse().unwrap_or(depth + 1);
+                if depth == 1 {
+                    top_level_index = Some(top_level_index.map_or(0, |i: u8| i + 1));
+                }
+                continue;
+            }
+            if rest.ends_with(" success") || rest.contains(" failed: ") {
+                depth -= 1;
+                continue;
+            }
+        }
+
+        if depth == 1 && top_level_index == Some(index) {
+            if let Some(name) = log.strip_prefix("Program log: Instruction: ") {
+                return Some(name.to_string());
+            }
+        }
+    }
+
+    None
+}
+
+/// Decodes the error of `send_and_confirm_transaction` and logs it.
+///
+/// Benign failures are logged and returned as `Ok(None)`.
+pub fn check_send_result(
+    program: JitoProgram,
+    result: Result<Signature, ClientError>,
+) -> anyhow::Result<Option<Signature>> {
+    match result {
+        Ok(sig) => {
+            log::info!("Transaction confirmed: {sig}");
+            Ok(Some(sig))
+        }
+        Err(e) => match InstructionFailure::from_client_error(program, &e) {
+            Some(failure) if failure.benign => {
+                log::info!("{failure}, treating as success");
+                Ok(None)
+            }
+            Some(failure) => {
+                log::error!("{failure}");
+                Err(anyhow::Error::new(e).context(failure.to_string()))
+            }
+            None => {
+                log::error!("Failed to send transaction: {:?}", e);
+                Err(anyhow::Error::new(e).context("Failed to send transaction"))
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instruction_name() {
+        let logs: Vec<String> = [
+            "Program ComputeBudget111111111111111111111111111111 invoke [1]",
+            "Program ComputeBudget111111111111111111111111111111 success",
+            "Program Vau1t6sLNxnzB7ZDsef8TLbPLfyZMYXH8WTNqUdm9g8 invoke [1]",
+            "Program log: Instruction: InitializeVaultUpdateStateTracker",
+            "Program 11111111111111111111111111111111 invoke [2]",
+            "Program 11111111111111111111111111111111 success",
+            "Program Vau1t6sLNxnzB7ZDsef8TLbPLfyZMYXH8WTNqUdm9g8 failed: custom program error: 0x3e8",
+        ]
+        .iter()
+        .map(|log| log.to_string())
+        .collect();
+
+        assert_eq!(
+            instruction_name(&logs, 1).as_deref(),
+            Some("InitializeVaultUpdateStateTracker")
+        );
+        assert_eq!(instruction_name(&logs, 0), None);
+    }
+
+    #[test]
+    fn test_benign_failures() {
+        let failure = InstructionFailure::new(
+            JitoProgram::Vault,
+            0,
+            &InstructionError::InvalidAccountOwner,
+            Some("CloseVaultUpdateStateTracker".to_string()),
+        );
+        assert!(failure.benign);
+        assert_eq!(
+            failure.to_string(),
+            "CloseVaultUpdateStateTracker (instruction 0) failed with InvalidAccountOwner: the \
+             tracker is already closed"
+        );
+
+        let failure = InstructionFailure::new(
+            JitoProgram::Vault,
+            0,
+            &InstructionError::InvalidAccountOwner,
+            None,
+        );
+        assert!(!failure.benign);
+        assert_eq!(failure.hint, None);
+    }
+}
@@ -0,0 +1,390 @@
+//! Summary of how the vaults were updated in an NCN epoch.
+
+use std::{collections::HashMap, fmt::Write, str::FromStr};
+
+use jito_vault_core::vault::Vault;
+use serde::Serialize;
+use solana_sdk::{clock::Slot, pubkey::Pubkey};
+
+use crate::history::{ActionRecord, ActionStatus};
+
+/// Output format of an [`EpochReport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+    Markdown,
+}
+
+impl FromStr for ReportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            "markdown" | "md" => Ok(Self::Markdown),
+            _ => Err(anyhow::anyhow!(
+                "Unknown report format {s}, expected json, csv or markdown"
+            )),
+        }
+    }
+}
+
+/// Whether a vault was updated in the epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VaultUpdateStatus {
+    Updated,
+    Missed,
+
+    /// The epoch is still running and nothing has been sent for the vault yet
+    Pending,
+
+    /// The epoch is still running and the vault is being updated
+    InProgress,
+
+    /// The vault was updated in a later epoch and the history has no record of this one
+    Unknown,
+}
+
+impl VaultUpdateStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Updated => "updated",
+            Self::Missed => "missed",
+            Self::Pending => "pending",
+            Self::InProgress => "in_progress",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VaultReport {
+    pub vault: String,
+    pub status: VaultUpdateStatus,
+
+    /// Slot the vault was fully updated at
+    pub finished_slot: Option<Slot>,
+
+    /// Slots between the start of the epoch and `finished_slot`
+    pub slots_after_boundary: Option<u64>,
+
+    /// Transactions sent for the vault in the epoch, from the history
+    pub transactions: usize,
+
+    /// Fees paid for `transactions`, in lamports
+    pub fees_lamports: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EpochReport {
+    pub ncn_epoch: u64,
+    pub epoch_length: u64,
+    pub start_slot: Slot,
+    pub vaults: Vec<VaultReport>,
+}
+
+impl EpochReport {
+    /// Builds the report of `ncn_epoch` from the vault accounts and the recorded actions.
+    ///
+    /// A vault counts as updated when its `last_full_state_update_slot` falls in the epoch or a
+    /// recorded close of the epoch succeeded. Vaults not updated yet are pending or in progress
+    /// while `ncn_epoch` is `current_epoch`, and missed once it has ended.
+    pub fn new(
+        ncn_epoch: u64,
+        current_epoch: u64,
+        epoch_length: u64,
+        vaults: &HashMap<Pubkey, Vault>,
+        records: &[ActionRecord],
+    ) -> Self {
+        let start_slot = ncn_epoch * epoch_length;
+
+        let mut vault_reports: Vec<VaultReport> = vaults
+            .iter()
+            .map(|(vault_pubkey, vault)| {
+                let records: Vec<&ActionRecord> = records
+                    .iter()
+                    .filter(|record| record.vault == *vault_pubkey && record.ncn_epoch == ncn_epoch)
+                    .collect();
+
+                let transactions = records.iter().map(|record| record.transactions.len()).sum();
+                let fees_lamports = records
+                    .iter()
+                    .flat_map(|record| record.transactions.iter())
+                    .filter_map(|transaction| transaction.fee)
+                    .sum();
+
+                // Closing the tracker is what updates the vault
+                let closed_slot = records
+                    .iter()
+                    .filter(|record| {
+                        record.status == ActionStatus::Succeeded && record.action == "Close"
+                    })
+                    .flat_map(|record| record.transactions.iter())
+                    .filter_map(|transaction| transaction.slot)
+                    .max();
+
+                let last_update_slot = vault.last_full_state_update_slot();
+                let last_update_epoch = last_update_slot / epoch_length;
+                let (status, finished_slot) = if last_update_epoch == ncn_epoch {
+                    (VaultUpdateStatus::Updated, Some(last_update_slot))
+                } else if closed_slot.is_some() {
+                    (VaultUpdateStatus::Updated, closed_slot)
+                } else if ncn_epoch >= current_epoch && records.is_empty() {
+                    (VaultUpdateStatus::Pending, None)
+                } else if ncn_epoch >= current_epoch {
+                    (VaultUpdateStatus::InProgress, None)
+                } else if last_update_epoch < ncn_epoch || !records.is_empty() {
+                    // Records of the epoch without a successful close mean the update was
+                    // attempted and did not finish in time, even if a later epoch updated the
+                    // vault
+                    (VaultUpdateStatus::Missed, None)
+                } else {
+                    (VaultUpdateStatus::Unknown, None)
+                };
+
+                VaultReport {
+                    vault: vault_pubkey.to_string(),
+                    status,
+                    finished_slot,
+                    slots_after_boundary: finished_slot.map(|slot| slot.saturating_sub(start_slot)),
+                    transactions,
+                    fees_lamports,
+                }
+            })
+            .collect();
+        vault_reports.sort_by(|a, b| a.vault.cmp(&b.vault));
+
+        Self {
+            ncn_epoch,
+            epoch_length,
+            start_slot,
+            vaults: vault_reports,
+        }
+    }
+
+    pub fn render(&self, format: ReportFormat) -> anyhow::Result<String> {
+        match format {
+            ReportFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            ReportFormat::Csv => Ok(self.to_csv()),
+            ReportFormat::Markdown => Ok(self.to_markdown()),
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        let mut out = String::from(
+            "ncn_epoch,vault,status,finished_slot,slots_after_boundary,transactions,\
+             fees_lamports\n",
+        );
+        for vault in self.vaults.iter() {
+            let _ = writeln!(
+                out,
+                "{},{},{},{},{},{},{}",
+                self.ncn_epoch,
+                vault.vault,
+                vault.status.as_str(),
+                display_option(vault.finished_slot),
+                display_option(vault.slots_after_boundary),
+                vault.transactions,
+                vault.fees_lamports
+            );
+        }
+
+        out
+    }
+
+    fn to_markdown(&self) -> String {
+        let updated = self.count(VaultUpdateStatus::Updated);
+        let missed = self.count(VaultUpdateStatus::Missed);
+        let pending =
+            self.count(VaultUpdateStatus::Pending) + self.count(VaultUpdateStatus::InProgress);
+        let unknown = self.count(VaultUpdateStatus::Unknown);
+        let transactions: usize = self.vaults.iter().map(|vault| vault.transactions).sum();
+        let fees_lamports: u64 = self.vaults.iter().map(|vault| vault.fees_lamports).sum();
+
+        let mut out = String::new();
+        let _ = writeln!(out, "# NCN epoch {}\n", self.ncn_epoch);
+        let _ = writeln!(
+            out,
+            "Starts at slot {} ({} slots per epoch). {updated} updated, {missed} missed, \
+             {pending} pending, {unknown} unknown. {transactions} transactions, {fees_lamports} \
+             lamports in fees.\n",
+            self.start_slot, self.epoch_length
+        );
+        let _ = writeln!(
+            out,
+            "| Vault | Status | Finished slot | Slots after boundary | Transactions | \
+             Fees (lamports) |"
+        );
+        let _ = writeln!(out, "|---|---|---|---|---|---|");
+        for vault in self.vaults.iter() {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} |",
+                vault.vault,
+                vault.status.as_str(),
+                display_option(vault.finished_slot),
+                display_option(vault.slots_after_boundary),
+                vault.transactions,
+                vault.fees_lamports
+            );
+        }
+
+        if missed > 0 {
+            let _ = writeln!(out, "\n## Missed\n");
+            for vault in self.vaults.iter() {
+                if vault.status == VaultUpdateStatus::Missed {
+                    let _ = writeln!(out, "- {}", vault.vault);
+                }
+            }
+        }
+
+        out
+    }
+
+    fn count(&self, status: VaultUpdateStatus) -> usize {
+        self.vaults
+            .iter()
+            .filter(|vault| vault.status == status)
+            .count()
+    }
+}
+
+fn display_option(value: Option<u64>) -> String {
+    value.map_or_else(String::new, |value| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::Signature;
+
+    use super::*;
+    use crate::history::TransactionRecord;
+
+    fn vault(last_full_state_update_slot: u64) -> Vault {
+        Vault::new(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            0,
+            Pubkey::new_unique(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            last_full_state_update_slot,
+        )
+        .unwrap()
+    }
+
+    fn record(vault: Pubkey, action: &str, slot: Slot) -> ActionRecord {
+        ActionRecord {
+            id: 0,
+            vault,
+            ncn_epoch: 3,
+            action: action.to_string(),
+            status: ActionStatus::Succeeded,
+            planned_at: 0,
+            duration_ms: None,
+            error: None,
+            transactions: vec![TransactionRecord {
+                signature: Signature::new_unique(),
+                slot: Some(slot),
+                fee: Some(5000),
+                compute_units: None,
+                compute_unit_price: None,
+                priority_fee: None,
+                cost: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_epoch_report() {
+        let updated = Pubkey::new_unique();
+        let missed = Pubkey::new_unique();
+        let later = Pubkey::new_unique();
+        let vaults = HashMap::from([
+            (updated, vault(340)),
+            (missed, vault(250)),
+            (later, vault(450)),
+        ]);
+        let records = vec![
+            record(updated, "Initialize", 310),
+            record(updated, "Close", 340),
+        ];
+
+        let report = EpochReport::new(3, 5, 100, &vaults, &records);
+        let find = |vault: &Pubkey| {
+            report
+                .vaults
+                .iter()
+                .find(|report| report.vault == vault.to_string())
+                .unwrap()
+        };
+
+        assert_eq!(report.start_slot, 300);
+        assert_eq!(find(&updated).status, VaultUpdateStatus::Updated);
+        assert_eq!(find(&updated).slots_after_boundary, Some(40));
+        assert_eq!(find(&updated).transactions, 2);
+        assert_eq!(find(&updated).fees_lamports, 10_000);
+        assert_eq!(find(&missed).status, VaultUpdateStatus::Missed);
+        assert_eq!(find(&later).status, VaultUpdateStatus::Unknown);
+
+        let csv = report.render(ReportFormat::Csv).unwrap();
+        assert_eq!(csv.lines().count(), 4);
+    }
+
+    #[test]
+    fn test_epoch_report_unfinished() {
+        let failed = Pubkey::new_unique();
+        let interrupted = Pubkey::new_unique();
+        let vaults = HashMap::from([(failed, vault(450)), (interrupted, vault(450))]);
+        let mut failed_close = record(failed, "Close", 390);
+        failed_close.status = ActionStatus::Failed;
+        let mut interrupted_crank = record(interrupted, "Crank", 320);
+        interrupted_crank.status = ActionStatus::Interrupted;
+        let records = vec![
+            record(failed, "Initialize", 310),
+            failed_close,
+            interrupted_crank,
+        ];
+
+        // Updated in epoch 4, after failing to in epoch 3
+        let report = EpochReport::new(3, 5, 100, &vaults, &records);
+        assert!(report
+            .vaults
+            .iter()
+            .all(|report| report.status == VaultUpdateStatus::Missed));
+    }
+
+    #[test]
+    fn test_epoch_report_current_epoch() {
+        let started = Pubkey::new_unique();
+        let waiting = Pubkey::new_unique();
+        let updated = Pubkey::new_unique();
+        let vaults = HashMap::from([
+            (started, vault(250)),
+            (waiting, vault(250)),
+            (updated, vault(320)),
+        ]);
+        let records = vec![record(started, "Initialize", 310)];
+
+        let report = EpochReport::new(3, 3, 100, &vaults, &records);
+        let find = |vault: &Pubkey| {
+            report
+                .vaults
+                .iter()
+                .find(|report| report.vault == vault.to_string())
+                .unwrap()
+                .status
+        };
+
+        assert_eq!(find(&started), VaultUpdateStatus::InProgress);
+        assert_eq!(find(&waiting), VaultUpdateStatus::Pending);
+        assert_eq!(find(&updated), VaultUpdateStatus::Updated);
+    }
+}
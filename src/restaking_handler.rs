@@ -1,70 +1,283 @@
-use jito_restaking_client::instructions::{InitializeNcnBuilder, InitializeOperatorBuilder};
-use jito_restaking_core::{ncn::Ncn, operator::Operator};
-use solana_client::nonblocking::rpc_client::RpcClient;
+use std::{path::Path, sync::Arc};
+
+use jito_bytemuck::AccountDeserialize;
+use jito_restaking_client::instructions::{
+    CooldownNcnVaultTicketBuilder, CooldownOperatorVaultTicketBuilder, InitializeNcnBuilder,
+    InitializeNcnOperatorStateBuilder, InitializeNcnVaultTicketBuilder, InitializeOperatorBuilder,
+    InitializeOperatorVaultTicketBuilder, NcnCooldownOperatorBuilder, NcnSetAdminBuilder,
+    NcnWarmupOperatorBuilder, OperatorCooldownNcnBuilder, OperatorSetAdminBuilder,
+    OperatorSetFeeBuilder, OperatorWarmupNcnBuilder, WarmupNcnVaultTicketBuilder,
+    WarmupOperatorVaultTicketBuilder,
+};
+use jito_restaking_core::{
+    config::Config, ncn::Ncn, ncn_operator_state::NcnOperatorState,
+    ncn_vault_ticket::NcnVaultTicket, operator::Operator,
+    operator_vault_ticket::OperatorVaultTicket,
+};
+use solana_client::{client_error::ClientError, nonblocking::rpc_client::RpcClient};
 use solana_sdk::{
-    commitment_config::CommitmentConfig, pubkey, pubkey::Pubkey, signature::Keypair,
-    signer::Signer, transaction::Transaction,
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::{Signer, SignerError},
+    system_program,
 };
+use thiserror::Error;
+
+use crate::{
+    compute_budget::ComputeUnitEstimator,
+    nonce_pool::NoncePool,
+    program_error::{InstructionFailure, JitoProgram},
+    signer::{resolve_base, sign_transaction, SharedSigner, WriteKeypairError},
+    transaction_export::TransactionExporter,
+};
+
+#[derive(Debug, Error)]
+pub enum RestakingHandlerError {
+    #[error("Failed to get latest blockhash: {0}")]
+    Blockhash(#[source] Box<ClientError>),
+
+    #[error("Failed to sign transaction: {0}")]
+    Sign(#[source] SignerError),
+
+    #[error("Failed to export transaction: {0}")]
+    Export(String),
+
+    #[error("Failed to lease a durable nonce: {0}")]
+    Nonce(String),
+
+    #[error("Failed to send transaction: {0}")]
+    SendTransaction(#[source] Box<ClientError>),
+
+    #[error("{failure}")]
+    Instruction {
+        failure: InstructionFailure,
+        #[source]
+        source: Box<ClientError>,
+    },
+
+    #[error("Failed to get account {address}: {source}")]
+    GetAccount {
+        address: Pubkey,
+        #[source]
+        source: Box<ClientError>,
+    },
 
-pub struct RestakingHandler<'a> {
+    #[error("Failed to deserialize account {address}: {source}")]
+    Deserialize {
+        address: Pubkey,
+        #[source]
+        source: ProgramError,
+    },
+
+    #[error("Restaking config vault program mismatch: expected {expected}, found {actual}")]
+    VaultProgramMismatch { expected: Pubkey, actual: Pubkey },
+
+    #[error(transparent)]
+    WriteKeypair(#[from] WriteKeypairError),
+}
+
+pub struct RestakingHandler {
     rpc_url: String,
-    payer: &'a Keypair,
+    commitment: CommitmentConfig,
+    payer: SharedSigner,
     restaking_program_id: Pubkey,
+    vault_program_id: Pubkey,
+
+    /// Exports transactions instead of signing them with the payer and sending them
+    exporter: Option<Arc<TransactionExporter>>,
+
+    /// Durable nonce accounts of the payer used by exported transactions
+    nonces: Option<Arc<NoncePool>>,
+
+    /// Sets the compute-unit limit of every transaction from a simulation
+    compute: Arc<ComputeUnitEstimator>,
 }
 
-impl<'a> RestakingHandler<'a> {
-    pub fn new(rpc_url: &str, payer: &'a Keypair, restaking_program_id: Pubkey) -> Self {
+impl RestakingHandler {
+    pub fn new(
+        rpc_url: &str,
+        commitment: CommitmentConfig,
+        payer: SharedSigner,
+        restaking_program_id: Pubkey,
+        vault_program_id: Pubkey,
+    ) -> Self {
         Self {
             rpc_url: rpc_url.to_string(),
+            commitment,
             payer,
             restaking_program_id,
+            vault_program_id,
+            exporter: None,
+            nonces: None,
+            compute: Arc::new(ComputeUnitEstimator::default()),
         }
     }
 
+    /// Exports the transactions of every operation instead of sending them, which then return no
+    /// signature.
+    pub fn set_exporter(&mut self, exporter: Arc<TransactionExporter>) {
+        self.exporter = Some(exporter);
+    }
+
+    /// Makes exported transactions advance a durable nonce of the payer instead of using a
+    /// recent blockhash, which expires before an offline or multisig signer gets to them.
+    pub fn set_nonce_pool(&mut self, nonces: Arc<NoncePool>) {
+        self.nonces = Some(nonces);
+    }
+
+    pub fn set_compute_unit_estimator(&mut self, compute: Arc<ComputeUnitEstimator>) {
+        self.compute = compute;
+    }
+
     fn get_rpc_client(&self) -> RpcClient {
-        RpcClient::new_with_commitment(self.rpc_url.clone(), CommitmentConfig::confirmed())
+        RpcClient::new_with_commitment(self.rpc_url.clone(), self.commitment)
     }
 
-    pub async fn initialize_config(&self) {
+    /// Signs `instructions` with the payer and `signers`, then sends and confirms the transaction,
+    /// or exports it if an exporter is set and returns no signature.
+    ///
+    /// The transaction is simulated as it is sent, nonce advance included, to set its compute-unit
+    /// limit. An exported transaction advances a durable nonce leased from the nonce pool, which
+    /// is required then.
+    async fn send_transaction(
+        &self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<Option<Signature>, RestakingHandlerError> {
         let rpc_client = self.get_rpc_client();
 
+        let mut instructions = instructions.to_vec();
+        let signers: Vec<&dyn Signer> = signers
+            .iter()
+            .map(|signer| *signer as &dyn Signer)
+            .collect();
+        if let Some(exporter) = self.exporter.as_ref() {
+            let nonces = self.nonces.as_ref().ok_or_else(|| {
+                RestakingHandlerError::Nonce(
+                    "exported transactions need durable nonce accounts".to_string(),
+                )
+            })?;
+            let lease = nonces
+                .acquire(&rpc_client, &self.payer.pubkey())
+                .await
+                .map_err(|e| RestakingHandlerError::Nonce(format!("{e:#}")))?
+                .ok_or_else(|| {
+                    RestakingHandlerError::Nonce(format!(
+                        "every nonce account of {} is in use",
+                        self.payer.pubkey()
+                    ))
+                })?;
+            instructions.insert(0, lease.advance_instruction());
+            let instructions = self
+                .compute
+                .with_compute_unit_limit(&rpc_client, instructions, &self.payer.pubkey(), None)
+                .await;
+            exporter
+                .export(&instructions, &self.payer.pubkey(), &signers, lease.nonce())
+                .map_err(|e| RestakingHandlerError::Export(format!("{e:#}")))?;
+            // The nonce only advances once the transaction is submitted
+            lease.keep();
+            return Ok(None);
+        }
+
+        let instructions = self
+            .compute
+            .with_compute_unit_limit(&rpc_client, instructions, &self.payer.pubkey(), None)
+            .await;
+
+        let blockhash = rpc_client
+            .get_latest_blockhash()
+            .await
+            .map_err(|e| RestakingHandlerError::Blockhash(Box::new(e)))?;
+        let tx = sign_transaction(&instructions, self.payer.as_ref(), &signers, blockhash)
+            .map_err(RestakingHandlerError::Sign)?;
+
+        let sig = rpc_client
+            .send_and_confirm_transaction(&tx)
+            .await
+            .map_err(|e| {
+                match InstructionFailure::from_client_error(JitoProgram::Restaking, &e) {
+                    Some(failure) => RestakingHandlerError::Instruction {
+                        failure,
+                        source: Box::new(e),
+                    },
+                    None => RestakingHandlerError::SendTransaction(Box::new(e)),
+                }
+            })?;
+        log::info!("Transaction confirmed: {sig}");
+
+        Ok(Some(sig))
+    }
+
+    /// Returns the address of the restaking `Config` account.
+    pub fn config_address(&self) -> Pubkey {
+        Config::find_program_address(&self.restaking_program_id).0
+    }
+
+    /// Fetches and deserializes the restaking `Config` account.
+    pub async fn get_config(&self) -> Result<Config, RestakingHandlerError> {
+        let rpc_client = self.get_rpc_client();
+
+        let address = self.config_address();
+        let account = rpc_client.get_account(&address).await.map_err(|e| {
+            RestakingHandlerError::GetAccount {
+                address,
+                source: Box::new(e),
+            }
+        })?;
+        let config = Config::try_from_slice_unchecked(&account.data)
+            .map_err(|source| RestakingHandlerError::Deserialize { address, source })?;
+
+        Ok(*config)
+    }
+
+    /// Initializes the restaking `Config` account and checks that the stored vault program
+    /// matches the one this handler was created with.
+    ///
+    /// Returns the transaction signature, `None` if it was exported.
+    pub async fn initialize_config(&self) -> Result<Option<Signature>, RestakingHandlerError> {
         let mut ix_builder = jito_restaking_client::instructions::InitializeConfigBuilder::new();
-        let config_address =
-            jito_restaking_core::config::Config::find_program_address(&self.restaking_program_id).0;
         ix_builder
-            .config(config_address)
+            .config(self.config_address())
             .admin(self.payer.pubkey())
-            .vault_program(pubkey!("BLCDL7LqxaYWxSEkayc4VYjs3iCNJJw8SQzsvEL2uVT"));
+            .vault_program(self.vault_program_id);
         let mut ix = ix_builder.instruction();
         ix.program_id = self.restaking_program_id;
 
-        let blockhash = rpc_client.get_latest_blockhash().await.expect("");
-        let tx = Transaction::new_signed_with_payer(
-            &[ix],
-            Some(&self.payer.pubkey()),
-            &[&self.payer],
-            blockhash,
-        );
-        rpc_client
-            .send_and_confirm_transaction(&tx)
-            .await
-            .expect("");
-    }
+        let sig = self.send_transaction(&[ix], &[]).await?;
+        if self.exporter.is_some() {
+            return Ok(sig);
+        }
 
-    pub async fn initialize_ncn(&self) {
-        let rpc_client = self.get_rpc_client();
+        let config = self.get_config().await?;
+        if config.vault_program != self.vault_program_id {
+            return Err(RestakingHandlerError::VaultProgramMismatch {
+                expected: self.vault_program_id,
+                actual: config.vault_program,
+            });
+        }
 
-        let base = Keypair::new();
+        Ok(sig)
+    }
+
+    /// Initializes a new `Ncn` account.
+    ///
+    /// Uses `base` if given, otherwise generates one and writes it to `base_keypair_out` if set.
+    /// Returns the address of the `Ncn` and the transaction signature, `None` if it was exported.
+    pub async fn initialize_ncn(
+        &self,
+        base: Option<&Keypair>,
+        base_keypair_out: Option<&Path>,
+    ) -> Result<(Pubkey, Option<Signature>), RestakingHandlerError> {
+        let base = resolve_base(base, base_keypair_out)?;
         let ncn = Ncn::find_program_address(&self.restaking_program_id, &base.pubkey()).0;
 
         let mut ix_builder = InitializeNcnBuilder::new();
         ix_builder
-            .config(
-                jito_restaking_core::config::Config::find_program_address(
-                    &self.restaking_program_id,
-                )
-                .0,
-            )
+            .config(self.config_address())
             .ncn(ncn)
             .admin(self.payer.pubkey())
             .base(base.pubkey())
@@ -72,50 +285,365 @@ impl<'a> RestakingHandler<'a> {
         let mut ix = ix_builder.instruction();
         ix.program_id = self.restaking_program_id;
 
-        let blockhash = rpc_client.get_latest_blockhash().await.expect("");
-        let tx = Transaction::new_signed_with_payer(
-            &[ix],
-            Some(&self.payer.pubkey()),
-            &[self.payer, &base],
-            blockhash,
-        );
-        rpc_client
-            .send_and_confirm_transaction(&tx)
-            .await
-            .expect("");
-    }
+        let sig = self.send_transaction(&[ix], &[&base]).await?;
 
-    pub async fn initialize_operator(&self) {
-        let rpc_client = self.get_rpc_client();
+        Ok((ncn, sig))
+    }
 
-        let base = Keypair::new();
+    /// Initializes a new `Operator` account.
+    ///
+    /// Uses `base` if given, otherwise generates one and writes it to `base_keypair_out` if set.
+    /// Returns the address of the `Operator` and the transaction signature, `None` if it was
+    /// exported.
+    pub async fn initialize_operator(
+        &self,
+        operator_fee_bps: u16,
+        base: Option<&Keypair>,
+        base_keypair_out: Option<&Path>,
+    ) -> Result<(Pubkey, Option<Signature>), RestakingHandlerError> {
+        let base = resolve_base(base, base_keypair_out)?;
         let operator = Operator::find_program_address(&self.restaking_program_id, &base.pubkey()).0;
 
         let mut ix_builder = InitializeOperatorBuilder::new();
         ix_builder
-            .config(
-                jito_restaking_core::config::Config::find_program_address(
-                    &self.restaking_program_id,
-                )
-                .0,
-            )
+            .config(self.config_address())
             .operator(operator)
             .admin(self.payer.pubkey())
             .base(base.pubkey())
+            .operator_fee_bps(operator_fee_bps)
             .instruction();
         let mut ix = ix_builder.instruction();
         ix.program_id = self.restaking_program_id;
 
-        let blockhash = rpc_client.get_latest_blockhash().await.expect("");
-        let tx = Transaction::new_signed_with_payer(
-            &[ix],
-            Some(&self.payer.pubkey()),
-            &[self.payer, &base],
-            blockhash,
-        );
-        rpc_client
-            .send_and_confirm_transaction(&tx)
-            .await
-            .expect("");
+        let sig = self.send_transaction(&[ix], &[&base]).await?;
+
+        Ok((operator, sig))
+    }
+
+    /// Initializes the `NcnOperatorState` between `ncn` and `operator`.
+    ///
+    /// Returns the address of the `NcnOperatorState` and the transaction signature, `None` if it
+    /// was exported.
+    pub async fn initialize_ncn_operator_state(
+        &self,
+        ncn: Pubkey,
+        operator: Pubkey,
+    ) -> Result<(Pubkey, Option<Signature>), RestakingHandlerError> {
+        let ncn_operator_state =
+            NcnOperatorState::find_program_address(&self.restaking_program_id, &ncn, &operator).0;
+
+        let mut ix_builder = InitializeNcnOperatorStateBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .ncn(ncn)
+            .operator(operator)
+            .ncn_operator_state(ncn_operator_state)
+            .admin(self.payer.pubkey())
+            .payer(self.payer.pubkey())
+            .system_program(system_program::id());
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.restaking_program_id;
+
+        let sig = self.send_transaction(&[ix], &[]).await?;
+
+        Ok((ncn_operator_state, sig))
+    }
+
+    /// Warms up `operator` on the NCN side of their `NcnOperatorState`.
+    pub async fn ncn_warmup_operator(
+        &self,
+        ncn: Pubkey,
+        operator: Pubkey,
+    ) -> Result<Option<Signature>, RestakingHandlerError> {
+        let ncn_operator_state =
+            NcnOperatorState::find_program_address(&self.restaking_program_id, &ncn, &operator).0;
+
+        let mut ix_builder = NcnWarmupOperatorBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .ncn(ncn)
+            .operator(operator)
+            .ncn_operator_state(ncn_operator_state)
+            .admin(self.payer.pubkey());
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.restaking_program_id;
+
+        self.send_transaction(&[ix], &[]).await
+    }
+
+    /// Cools down `operator` on the NCN side of their `NcnOperatorState`.
+    pub async fn ncn_cooldown_operator(
+        &self,
+        ncn: Pubkey,
+        operator: Pubkey,
+    ) -> Result<Option<Signature>, RestakingHandlerError> {
+        let ncn_operator_state =
+            NcnOperatorState::find_program_address(&self.restaking_program_id, &ncn, &operator).0;
+
+        let mut ix_builder = NcnCooldownOperatorBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .ncn(ncn)
+            .operator(operator)
+            .ncn_operator_state(ncn_operator_state)
+            .admin(self.payer.pubkey());
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.restaking_program_id;
+
+        self.send_transaction(&[ix], &[]).await
+    }
+
+    /// Warms up `ncn` on the operator side of their `NcnOperatorState`.
+    pub async fn operator_warmup_ncn(
+        &self,
+        ncn: Pubkey,
+        operator: Pubkey,
+    ) -> Result<Option<Signature>, RestakingHandlerError> {
+        let ncn_operator_state =
+            NcnOperatorState::find_program_address(&self.restaking_program_id, &ncn, &operator).0;
+
+        let mut ix_builder = OperatorWarmupNcnBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .ncn(ncn)
+            .operator(operator)
+            .ncn_operator_state(ncn_operator_state)
+            .admin(self.payer.pubkey());
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.restaking_program_id;
+
+        self.send_transaction(&[ix], &[]).await
+    }
+
+    /// Cools down `ncn` on the operator side of their `NcnOperatorState`.
+    pub async fn operator_cooldown_ncn(
+        &self,
+        ncn: Pubkey,
+        operator: Pubkey,
+    ) -> Result<Option<Signature>, RestakingHandlerError> {
+        let ncn_operator_state =
+            NcnOperatorState::find_program_address(&self.restaking_program_id, &ncn, &operator).0;
+
+        let mut ix_builder = OperatorCooldownNcnBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .ncn(ncn)
+            .operator(operator)
+            .ncn_operator_state(ncn_operator_state)
+            .admin(self.payer.pubkey());
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.restaking_program_id;
+
+        self.send_transaction(&[ix], &[]).await
+    }
+
+    /// Initializes the `NcnVaultTicket` between `ncn` and `vault`.
+    ///
+    /// Returns the address of the `NcnVaultTicket` and the transaction signature, `None` if it was
+    /// exported.
+    pub async fn initialize_ncn_vault_ticket(
+        &self,
+        ncn: Pubkey,
+        vault: Pubkey,
+    ) -> Result<(Pubkey, Option<Signature>), RestakingHandlerError> {
+        let ncn_vault_ticket =
+            NcnVaultTicket::find_program_address(&self.restaking_program_id, &ncn, &vault).0;
+
+        let mut ix_builder = InitializeNcnVaultTicketBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .ncn(ncn)
+            .vault(vault)
+            .ncn_vault_ticket(ncn_vault_ticket)
+            .admin(self.payer.pubkey())
+            .payer(self.payer.pubkey())
+            .system_program(system_program::id());
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.restaking_program_id;
+
+        let sig = self.send_transaction(&[ix], &[]).await?;
+
+        Ok((ncn_vault_ticket, sig))
+    }
+
+    /// Warms up the `NcnVaultTicket` between `ncn` and `vault`.
+    pub async fn warmup_ncn_vault_ticket(
+        &self,
+        ncn: Pubkey,
+        vault: Pubkey,
+    ) -> Result<Option<Signature>, RestakingHandlerError> {
+        let ncn_vault_ticket =
+            NcnVaultTicket::find_program_address(&self.restaking_program_id, &ncn, &vault).0;
+
+        let mut ix_builder = WarmupNcnVaultTicketBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .ncn(ncn)
+            .vault(vault)
+            .ncn_vault_ticket(ncn_vault_ticket)
+            .admin(self.payer.pubkey());
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.restaking_program_id;
+
+        self.send_transaction(&[ix], &[]).await
+    }
+
+    /// Cools down the `NcnVaultTicket` between `ncn` and `vault`.
+    pub async fn cooldown_ncn_vault_ticket(
+        &self,
+        ncn: Pubkey,
+        vault: Pubkey,
+    ) -> Result<Option<Signature>, RestakingHandlerError> {
+        let ncn_vault_ticket =
+            NcnVaultTicket::find_program_address(&self.restaking_program_id, &ncn, &vault).0;
+
+        let mut ix_builder = CooldownNcnVaultTicketBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .ncn(ncn)
+            .vault(vault)
+            .ncn_vault_ticket(ncn_vault_ticket)
+            .admin(self.payer.pubkey());
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.restaking_program_id;
+
+        self.send_transaction(&[ix], &[]).await
+    }
+
+    /// Initializes the `OperatorVaultTicket` between `operator` and `vault`.
+    ///
+    /// Returns the address of the `OperatorVaultTicket` and the transaction signature, `None` if it
+    /// was exported.
+    pub async fn initialize_operator_vault_ticket(
+        &self,
+        operator: Pubkey,
+        vault: Pubkey,
+    ) -> Result<(Pubkey, Option<Signature>), RestakingHandlerError> {
+        let operator_vault_ticket = OperatorVaultTicket::find_program_address(
+            &self.restaking_program_id,
+            &operator,
+            &vault,
+        )
+        .0;
+
+        let mut ix_builder = InitializeOperatorVaultTicketBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .operator(operator)
+            .vault(vault)
+            .operator_vault_ticket(operator_vault_ticket)
+            .admin(self.payer.pubkey())
+            .payer(self.payer.pubkey())
+            .system_program(system_program::id());
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.restaking_program_id;
+
+        let sig = self.send_transaction(&[ix], &[]).await?;
+
+        Ok((operator_vault_ticket, sig))
+    }
+
+    /// Warms up the `OperatorVaultTicket` between `operator` and `vault`.
+    pub async fn warmup_operator_vault_ticket(
+        &self,
+        operator: Pubkey,
+        vault: Pubkey,
+    ) -> Result<Option<Signature>, RestakingHandlerError> {
+        let operator_vault_ticket = OperatorVaultTicket::find_program_address(
+            &self.restaking_program_id,
+            &operator,
+            &vault,
+        )
+        .0;
+
+        let mut ix_builder = WarmupOperatorVaultTicketBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .operator(operator)
+            .vault(vault)
+            .operator_vault_ticket(operator_vault_ticket)
+            .admin(self.payer.pubkey());
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.restaking_program_id;
+
+        self.send_transaction(&[ix], &[]).await
+    }
+
+    /// Cools down the `OperatorVaultTicket` between `operator` and `vault`.
+    pub async fn cooldown_operator_vault_ticket(
+        &self,
+        operator: Pubkey,
+        vault: Pubkey,
+    ) -> Result<Option<Signature>, RestakingHandlerError> {
+        let operator_vault_ticket = OperatorVaultTicket::find_program_address(
+            &self.restaking_program_id,
+            &operator,
+            &vault,
+        )
+        .0;
+
+        let mut ix_builder = CooldownOperatorVaultTicketBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .operator(operator)
+            .vault(vault)
+            .operator_vault_ticket(operator_vault_ticket)
+            .admin(self.payer.pubkey());
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.restaking_program_id;
+
+        self.send_transaction(&[ix], &[]).await
+    }
+
+    /// Transfers the admin of `ncn` from the payer to `new_admin`, which must also sign.
+    pub async fn ncn_set_admin(
+        &self,
+        ncn: Pubkey,
+        new_admin: &Keypair,
+    ) -> Result<Option<Signature>, RestakingHandlerError> {
+        let mut ix_builder = NcnSetAdminBuilder::new();
+        ix_builder
+            .ncn(ncn)
+            .old_admin(self.payer.pubkey())
+            .new_admin(new_admin.pubkey());
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.restaking_program_id;
+
+        self.send_transaction(&[ix], &[new_admin]).await
+    }
+
+    /// Transfers the admin of `operator` from the payer to `new_admin`, which must also sign.
+    pub async fn operator_set_admin(
+        &self,
+        operator: Pubkey,
+        new_admin: &Keypair,
+    ) -> Result<Option<Signature>, RestakingHandlerError> {
+        let mut ix_builder = OperatorSetAdminBuilder::new();
+        ix_builder
+            .operator(operator)
+            .old_admin(self.payer.pubkey())
+            .new_admin(new_admin.pubkey());
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.restaking_program_id;
+
+        self.send_transaction(&[ix], &[new_admin]).await
+    }
+
+    /// Sets the fee of `operator` in basis points.
+    pub async fn operator_set_fee(
+        &self,
+        operator: Pubkey,
+        new_fee_bps: u16,
+    ) -> Result<Option<Signature>, RestakingHandlerError> {
+        let mut ix_builder = OperatorSetFeeBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .operator(operator)
+            .admin(self.payer.pubkey())
+            .new_fee_bps(new_fee_bps);
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.restaking_program_id;
+
+        self.send_transaction(&[ix], &[]).await
     }
 }
@@ -0,0 +1,496 @@
+//! Payer signers loaded from keypair files, environment variables, stdin, the Solana CLI config or
+//! a remote signing service.
+
+use std::{
+    fmt,
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Deserialize;
+use serde_json::json;
+use solana_cli_config::{Config, CONFIG_FILE};
+use solana_sdk::{
+    bs58,
+    hash::Hash,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, write_keypair, Keypair, Signature},
+    signer::{null_signer::NullSigner, Signer, SignerError},
+    transaction::Transaction,
+};
+use thiserror::Error;
+
+/// Signer shared by the handlers and the tasks they run in.
+pub type SharedSigner = Arc<dyn Signer + Send + Sync>;
+
+/// Where to load a signer from.
+///
+/// Parsed from `env:NAME`, `stdin` (or `-`), `config` (or `config:PATH`), `pubkey:ADDRESS`, an
+/// `http://` or `https://` URL, and a keypair file path otherwise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignerSource {
+    /// Keypair file, `~` and environment variables in the path are expanded
+    File(String),
+
+    /// Base58 or JSON array keypair in an environment variable
+    Env(String),
+
+    /// Base58 or JSON array keypair read from stdin
+    Stdin,
+
+    /// `keypair_path` of a Solana CLI config file, the default one if `None`
+    CliConfig(Option<String>),
+
+    /// Remote signing service, see [`RemoteSigner`]
+    Remote(String),
+
+    /// Public key only, for payers that sign exported transactions elsewhere
+    Pubkey(Pubkey),
+}
+
+impl FromStr for SignerSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(name) = s.strip_prefix("env:") {
+            if name.is_empty() {
+                anyhow::bail!("Expected env:NAME, got {s}");
+            }
+            Ok(Self::Env(name.to_string()))
+        } else if s == "stdin" || s == "-" {
+            Ok(Self::Stdin)
+        } else if s == "config" {
+            Ok(Self::CliConfig(None))
+        } else if let Some(path) = s.strip_prefix("config:") {
+            Ok(Self::CliConfig(Some(path.to_string())))
+        } else if let Some(pubkey) = s.strip_prefix("pubkey:") {
+            Ok(Self::Pubkey(Pubkey::from_str(pubkey)?))
+        } else if s.starts_with("http://") || s.starts_with("https://") {
+            Ok(Self::Remote(s.trim_end_matches('/').to_string()))
+        } else {
+            Ok(Self::File(s.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for SignerSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => f.write_str(path),
+            Self::Env(name) => write!(f, "env:{name}"),
+            Self::Stdin => f.write_str("stdin"),
+            Self::CliConfig(None) => f.write_str("config"),
+            Self::CliConfig(Some(path)) => write!(f, "config:{path}"),
+            Self::Remote(url) => f.write_str(url),
+            Self::Pubkey(pubkey) => write!(f, "pubkey:{pubkey}"),
+        }
+    }
+}
+
+impl SignerSource {
+    pub async fn load(&self) -> anyhow::Result<SharedSigner> {
+        match self {
+            Self::File(path) => Ok(Arc::new(read_keypair_path(path)?)),
+            Self::Env(name) => {
+                let value = std::env::var(name)
+                    .with_context(|| format!("Failed to read keypair from ${name}"))?;
+                let keypair = keypair_from_str(&value).with_context(|| {
+                    format!("${name} does not hold a base58 or JSON array keypair")
+                })?;
+                Ok(Arc::new(keypair))
+            }
+            Self::Stdin => {
+                let value = std::io::read_to_string(std::io::stdin())
+                    .context("Failed to read keypair from stdin")?;
+                let keypair = keypair_from_str(&value)
+                    .context("stdin does not hold a base58 or JSON array keypair")?;
+                Ok(Arc::new(keypair))
+            }
+            Self::CliConfig(config_file) => {
+                let (config_file, config) = match config_file {
+                    Some(config_file) => {
+                        let config_file = expand_path(config_file)?;
+                        let config = Config::load(&config_file).with_context(|| {
+                            format!("Failed to read Solana CLI config {config_file}")
+                        })?;
+                        (config_file, config)
+                    }
+                    None => {
+                        let config_file = CONFIG_FILE
+                            .as_ref()
+                            .context("Failed to locate the Solana CLI config, no home directory")?;
+                        // The Solana CLI falls back to its default keypair without a config
+                        (
+                            config_file.clone(),
+                            Config::load(config_file).unwrap_or_default(),
+                        )
+                    }
+                };
+                let keypair = read_keypair_path(&config.keypair_path).with_context(|| {
+                    format!("Failed to read the keypair of Solana CLI config {config_file}")
+                })?;
+                Ok(Arc::new(keypair))
+            }
+            Self::Remote(url) => Ok(Arc::new(RemoteSigner::connect(url).await?)),
+            Self::Pubkey(pubkey) => Ok(Arc::new(NullSigner::new(pubkey))),
+        }
+    }
+}
+
+/// Expands a leading `~` to `$HOME`, and `$NAME` and `${NAME}` to the environment variables.
+pub fn expand_path(path: &str) -> anyhow::Result<String> {
+    let mut expanded = String::new();
+    let mut rest = path;
+    if path == "~" || path.starts_with("~/") {
+        let home = std::env::var("HOME")
+            .with_context(|| format!("Failed to expand ~ in {path}, $HOME is not set"))?;
+        expanded.push_str(&home);
+        rest = &path[1..];
+    }
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, remainder) = match after.strip_prefix('{') {
+            Some(braced) => {
+                let end = braced
+                    .find('}')
+                    .with_context(|| format!("Failed to expand {path}, missing }}"))?;
+                (&braced[..end], &braced[end + 1..])
+            }
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            }
+        };
+
+        if name.is_empty() {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        let value = std::env::var(name)
+            .with_context(|| format!("Failed to expand {path}, ${name} is not set"))?;
+        expanded.push_str(&value);
+        rest = remainder;
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+/// Reads the keypair file at `path` after expanding it.
+fn read_keypair_path(path: &str) -> anyhow::Result<Keypair> {
+    let expanded = expand_path(path)?;
+    read_keypair_file(&expanded).map_err(|e| {
+        if expanded == path {
+            anyhow::anyhow!("Failed to read keypair {path}: {e}")
+        } else {
+            anyhow::anyhow!("Failed to read keypair {path} (expanded to {expanded}): {e}")
+        }
+    })
+}
+
+/// Parses a keypair from its base58 string or the JSON array of its bytes.
+fn keypair_from_str(s: &str) -> anyhow::Result<Keypair> {
+    let s = s.trim();
+    let bytes: Vec<u8> = if s.starts_with('[') {
+        serde_json::from_str(s)?
+    } else {
+        bs58::decode(s).into_vec()?
+    };
+
+    Ok(Keypair::from_bytes(&bytes)?)
+}
+
+#[derive(Deserialize)]
+struct PubkeyResponse {
+    pubkey: String,
+}
+
+#[derive(Deserialize)]
+struct SignResponse {
+    signature: String,
+}
+
+/// Signs through an HTTP signing service.
+///
+/// The service answers `GET {url}/pubkey` with `{"pubkey": base58}` and
+/// `POST {url}/sign {"pubkey": base58, "message": base64}` with `{"signature": base58}`.
+/// Signatures are checked against the public key before they are used.
+pub struct RemoteSigner {
+    url: String,
+    pubkey: Pubkey,
+    client: reqwest::Client,
+}
+
+impl RemoteSigner {
+    /// Fetches the public key of the service at `url`.
+    pub async fn connect(url: &str) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+        let response: PubkeyResponse = client
+            .get(format!("{url}/pubkey"))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to get the public key of remote signer {url}"))?
+            .json()
+            .await
+            .with_context(|| format!("Invalid public key response from remote signer {url}"))?;
+
+        Ok(Self {
+            url: url.to_string(),
+            pubkey: Pubkey::from_str(&response.pubkey)?,
+            client,
+        })
+    }
+
+    async fn sign(&self, message: &[u8]) -> anyhow::Result<Signature> {
+        let response: SignResponse = self
+            .client
+            .post(format!("{}/sign", self.url))
+            .json(&json!({
+                "pubkey": self.pubkey.to_string(),
+                "message": STANDARD.encode(message),
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let signature = Signature::from_str(&response.signature)?;
+        if !signature.verify(self.pubkey.as_ref(), message) {
+            anyhow::bail!("Remote signer returned an invalid signature");
+        }
+
+        Ok(signature)
+    }
+}
+
+impl Signer for RemoteSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    /// Blocks the current worker thread of the multi-threaded Tokio runtime until the service
+    /// answers. A current-thread runtime can not be blocked, signing fails there.
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let handle = tokio::runtime::Handle::try_current()
+            .map_err(|_| SignerError::Custom("remote signer needs a Tokio runtime".to_string()))?;
+        if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::CurrentThread {
+            return Err(SignerError::Custom(
+                "remote signer needs a multi-threaded Tokio runtime".to_string(),
+            ));
+        }
+
+        tokio::task::block_in_place(|| handle.block_on(self.sign(message))).map_err(|e| {
+            SignerError::Connection(format!("Remote signer {} failed: {e:#}", self.url))
+        })
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// A generated base keypair could not be written out.
+#[derive(Debug, Error)]
+#[error("Failed to write base keypair to {}: {message}", path.display())]
+pub struct WriteKeypairError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+/// Returns `base` if given, otherwise generates a new base keypair.
+///
+/// A generated keypair is written to `base_keypair_out` when set, so the PDA derived from it can
+/// be found again later. An existing file is never overwritten, it may hold a keypair in use.
+pub fn resolve_base(
+    base: Option<&Keypair>,
+    base_keypair_out: Option<&Path>,
+) -> Result<Keypair, WriteKeypairError> {
+    if let Some(base) = base {
+        return Ok(base.insecure_clone());
+    }
+
+    let base = Keypair::new();
+    if let Some(path) = base_keypair_out {
+        let error = |message: String| WriteKeypairError {
+            path: path.to_path_buf(),
+            message,
+        };
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path).map_err(|e| error(e.to_string()))?;
+        write_keypair(&base, &mut file).map_err(|e| error(e.to_string()))?;
+        log::info!("Wrote base keypair {} to {}", base.pubkey(), path.display());
+    }
+
+    Ok(base)
+}
+
+/// Builds a transaction paid by `payer` and signed by it and `signers`.
+///
+/// Unlike `Transaction::new_signed_with_payer`, a signer that fails returns an error instead of
+/// panicking.
+pub fn sign_transaction(
+    instructions: &[Instruction],
+    payer: &dyn Signer,
+    signers: &[&dyn Signer],
+    blockhash: Hash,
+) -> Result<Transaction, SignerError> {
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+
+    let mut tx = Transaction::new_with_payer(instructions, Some(&payer.try_pubkey()?));
+    tx.try_sign(&all_signers, blockhash)?;
+
+    Ok(tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server;
+
+    #[test]
+    fn test_resolve_base() {
+        let path = std::env::temp_dir().join(format!("base-{}.json", Pubkey::new_unique()));
+
+        let base = resolve_base(None, Some(&path)).unwrap();
+        assert_eq!(read_keypair_file(&path).unwrap(), base);
+
+        // The keypair written first is kept
+        let error = resolve_base(None, Some(&path)).unwrap_err();
+        assert_eq!(error.path, path);
+        assert_eq!(read_keypair_file(&path).unwrap(), base);
+        assert_eq!(resolve_base(Some(&base), Some(&path)).unwrap(), base);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Serves the remote signer protocol for `keypair` on a local port and returns its URL.
+    async fn serve_signer(keypair: Keypair) -> String {
+        test_server::serve(move |head, body| {
+            if head.starts_with("GET /pubkey") {
+                json!({ "pubkey": keypair.pubkey().to_string() })
+            } else {
+                let request: serde_json::Value = serde_json::from_str(body).unwrap();
+                let message = STANDARD
+                    .decode(request["message"].as_str().unwrap())
+                    .unwrap();
+                json!({ "signature": keypair.sign_message(&message).to_string() })
+            }
+            .to_string()
+        })
+        .await
+    }
+
+    #[test]
+    fn test_expand_path() {
+        std::env::set_var("CHRONO_CRANK_TEST_DIR", "keys");
+        let home = std::env::var("HOME").unwrap();
+
+        assert_eq!(
+            expand_path("~/.config/solana/id.json").unwrap(),
+            format!("{home}/.config/solana/id.json")
+        );
+        assert_eq!(
+            expand_path("$CHRONO_CRANK_TEST_DIR/id.json").unwrap(),
+            "keys/id.json"
+        );
+        assert_eq!(
+            expand_path("/a/${CHRONO_CRANK_TEST_DIR}_1/~").unwrap(),
+            "/a/keys_1/~"
+        );
+        assert_eq!(expand_path("cost$").unwrap(), "cost$");
+
+        let e = expand_path("$CHRONO_CRANK_TEST_UNSET/id.json").unwrap_err();
+        assert!(e
+            .to_string()
+            .contains("$CHRONO_CRANK_TEST_UNSET is not set"));
+    }
+
+    #[test]
+    fn test_keypair_from_str() {
+        let keypair = Keypair::new();
+        let json = serde_json::to_string(&keypair.to_bytes().to_vec()).unwrap();
+
+        assert_eq!(keypair_from_str(&json).unwrap(), keypair);
+        assert_eq!(
+            keypair_from_str(&format!("{}\n", keypair.to_base58_string())).unwrap(),
+            keypair
+        );
+        assert!(keypair_from_str("[1, 2, 3]").is_err());
+    }
+
+    #[test]
+    fn test_signer_source() {
+        assert_eq!(
+            "env:PAYER".parse::<SignerSource>().unwrap(),
+            SignerSource::Env("PAYER".to_string())
+        );
+        assert_eq!(
+            "https://signer.example/".parse::<SignerSource>().unwrap(),
+            SignerSource::Remote("https://signer.example".to_string())
+        );
+        assert_eq!(
+            "~/payer.json".parse::<SignerSource>().unwrap(),
+            SignerSource::File("~/payer.json".to_string())
+        );
+        assert_eq!("-".parse::<SignerSource>().unwrap(), SignerSource::Stdin);
+        assert_eq!(
+            "config".parse::<SignerSource>().unwrap(),
+            SignerSource::CliConfig(None)
+        );
+        let pubkey = Pubkey::new_unique();
+        assert_eq!(
+            format!("pubkey:{pubkey}").parse::<SignerSource>().unwrap(),
+            SignerSource::Pubkey(pubkey)
+        );
+        assert!("env:".parse::<SignerSource>().is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_remote_signer() {
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+        let url = serve_signer(keypair).await;
+
+        let signer = SignerSource::Remote(url).load().await.unwrap();
+        assert_eq!(signer.pubkey(), pubkey);
+
+        let tx = sign_transaction(&[], signer.as_ref(), &[], Hash::new_unique()).unwrap();
+        assert!(tx.verify().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer_current_thread() {
+        let signer = RemoteSigner {
+            url: "http://127.0.0.1:1".to_string(),
+            pubkey: Pubkey::new_unique(),
+            client: reqwest::Client::new(),
+        };
+
+        assert!(matches!(
+            signer.try_sign_message(b"message"),
+            Err(SignerError::Custom(_))
+        ));
+    }
+}
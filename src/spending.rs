@@ -0,0 +1,230 @@
+//! Per-epoch spending budgets of the sponsored cranks and the metrics reporting what was spent.
+
+use std::{fmt::Write as _, fs, path::Path};
+
+use anyhow::Context;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    history::{OverheadCost, VaultCost},
+    payer_monitor::estimate_cost,
+    vault_state_manager::VaultAction,
+};
+
+/// Lamports the cranker may spend in an NCN epoch, on all vaults and on each of them.
+///
+/// The epoch budget also covers the overhead of the cranker, the nonce accounts of its payers.
+///
+/// Only initializations are held back: a tracker already initialized is always cranked and
+/// closed, so the vault is not left half updated and the tracker rent comes back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpendingBudget {
+    pub epoch: Option<u64>,
+    pub vault: Option<u64>,
+}
+
+impl SpendingBudget {
+    /// Returns why `vault` may not start an update expected to spend `expected` lamports, if the
+    /// overall budget or its own can not cover it.
+    pub fn exhausted(
+        &self,
+        costs: &[VaultCost],
+        overhead: &OverheadCost,
+        vault: &Pubkey,
+        expected: u64,
+    ) -> Option<&'static str> {
+        let expected = expected as i64;
+        let spent: i64 = costs.iter().map(VaultCost::total).sum::<i64>() + overhead.total();
+        if self
+            .epoch
+            .is_some_and(|budget| spent + expected > budget as i64)
+        {
+            return Some("the epoch budget can not cover another update");
+        }
+
+        let vault_spent = costs
+            .iter()
+            .find(|cost| cost.vault == *vault)
+            .map_or(0, VaultCost::total);
+        if self
+            .vault
+            .is_some_and(|budget| vault_spent + expected > budget as i64)
+        {
+            return Some("the vault's epoch budget can not cover another update");
+        }
+
+        None
+    }
+}
+
+/// Returns the lamports a full update of a vault with `operator_count` operators is expected to
+/// spend at its peak, before closing the tracker gets the rent back.
+pub fn update_cost(operator_count: u64, tracker_rent: u64) -> u64 {
+    estimate_cost(
+        &[
+            (VaultAction::Initialize, operator_count),
+            (VaultAction::Crank, operator_count),
+            (VaultAction::Close, operator_count),
+        ],
+        tracker_rent,
+    )
+}
+
+/// Renders the spending of `ncn_epoch` in the Prometheus text format.
+pub fn render_metrics(
+    ncn_epoch: u64,
+    costs: &[VaultCost],
+    overhead: &OverheadCost,
+    budget: &SpendingBudget,
+) -> String {
+    let mut out = String::new();
+    let metrics: [(&str, &str, fn(&VaultCost) -> i64); 4] = [
+        (
+            "chrono_crank_vault_spent_lamports",
+            "Lamports spent on the vault in the NCN epoch, fees and unreturned rent",
+            VaultCost::total,
+        ),
+        (
+            "chrono_crank_vault_fees_lamports",
+            "Transaction fees paid for the vault in the NCN epoch, priority fees included",
+            |cost| cost.fees as i64,
+        ),
+        (
+            "chrono_crank_vault_priority_fees_lamports",
+            "Priority fees paid for the vault in the NCN epoch",
+            |cost| cost.priority_fees as i64,
+        ),
+        (
+            "chrono_crank_vault_rent_lamports",
+            "Rent paid for the vault in the NCN epoch and not got back yet",
+            |cost| cost.rent,
+        ),
+    ];
+    for (name, help, value) in metrics {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} gauge");
+        for cost in costs {
+            let _ = writeln!(
+                out,
+                "{name}{{vault=\"{}\",ncn_epoch=\"{ncn_epoch}\"}} {}",
+                cost.vault,
+                value(cost)
+            );
+        }
+    }
+
+    let _ = writeln!(
+        out,
+        "# HELP chrono_crank_overhead_spent_lamports Lamports spent on the nonce accounts of the \
+         payers in the NCN epoch\n\
+         # TYPE chrono_crank_overhead_spent_lamports gauge\n\
+         chrono_crank_overhead_spent_lamports{{ncn_epoch=\"{ncn_epoch}\"}} {}",
+        overhead.total()
+    );
+    let spent: i64 = costs.iter().map(VaultCost::total).sum::<i64>() + overhead.total();
+    let _ = writeln!(
+        out,
+        "# HELP chrono_crank_epoch_spent_lamports Lamports spent in the NCN epoch, on all vaults \
+         and overhead\n\
+         # TYPE chrono_crank_epoch_spent_lamports gauge\n\
+         chrono_crank_epoch_spent_lamports{{ncn_epoch=\"{ncn_epoch}\"}} {spent}"
+    );
+    for (name, help, budget) in [
+        (
+            "chrono_crank_epoch_budget_lamports",
+            "Lamports the cranker may spend on all vaults in an NCN epoch",
+            budget.epoch,
+        ),
+        (
+            "chrono_crank_vault_epoch_budget_lamports",
+            "Lamports the cranker may spend on each vault in an NCN epoch",
+            budget.vault,
+        ),
+    ] {
+        if let Some(budget) = budget {
+            let _ = writeln!(
+                out,
+                "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {budget}"
+            );
+        }
+    }
+
+    out
+}
+
+/// Writes the metrics of `ncn_epoch` to `path`, replacing it at once so a collector reading it
+/// never sees a partial file.
+pub fn write_metrics(
+    path: &Path,
+    ncn_epoch: u64,
+    costs: &[VaultCost],
+    overhead: &OverheadCost,
+    budget: &SpendingBudget,
+) -> anyhow::Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, render_metrics(ncn_epoch, costs, overhead, budget))
+        .with_context(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to write {}", path.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget() {
+        let cost = |vault: Pubkey, fees: u64, rent: i64| VaultCost {
+            vault,
+            ncn_epoch: 7,
+            transactions: 1,
+            fees,
+            priority_fees: 0,
+            rent,
+        };
+        let vault = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let costs = vec![cost(vault, 10_000, 1_000_000), cost(other, 20_000, 0)];
+        let none = OverheadCost::default();
+
+        assert_eq!(
+            SpendingBudget::default().exhausted(&costs, &none, &vault, 5_000),
+            None
+        );
+        let budget = SpendingBudget {
+            epoch: Some(2_000_000),
+            vault: Some(1_020_000),
+        };
+        assert_eq!(budget.exhausted(&costs, &none, &vault, 10_000), None);
+        assert_eq!(
+            budget.exhausted(&costs, &none, &vault, 10_001),
+            Some("the vault's epoch budget can not cover another update")
+        );
+        assert_eq!(budget.exhausted(&costs, &none, &other, 10_000), None);
+        assert_eq!(budget.exhausted(&[], &none, &vault, 10_000), None);
+        let budget = SpendingBudget {
+            epoch: Some(1_040_000),
+            vault: None,
+        };
+        let overhead = OverheadCost {
+            transactions: 1,
+            fees: 5_000,
+            rent: 5_000,
+        };
+        assert_eq!(budget.exhausted(&costs, &none, &other, 10_000), None);
+        assert_eq!(
+            budget.exhausted(&costs, &overhead, &other, 1),
+            Some("the epoch budget can not cover another update")
+        );
+        assert_eq!(update_cost(3, 2_000_000), 2_000_000 + 5 * 5_000);
+
+        let metrics = render_metrics(7, &costs, &overhead, &budget);
+        assert!(metrics.contains(&format!(
+            "chrono_crank_vault_spent_lamports{{vault=\"{vault}\",ncn_epoch=\"7\"}} 1010000\n"
+        )));
+        assert!(metrics.contains("chrono_crank_overhead_spent_lamports{ncn_epoch=\"7\"} 10000\n"));
+        assert!(metrics.contains("chrono_crank_epoch_spent_lamports{ncn_epoch=\"7\"} 1040000\n"));
+        assert!(metrics.contains("chrono_crank_epoch_budget_lamports 1040000\n"));
+        assert!(!metrics.contains("chrono_crank_vault_epoch_budget_lamports"));
+    }
+}
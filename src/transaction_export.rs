@@ -0,0 +1,248 @@
+//! Unsigned transactions written out for offline or multisig signing instead of being sent.
+
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::Path,
+    str::FromStr,
+    sync::Mutex,
+};
+
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::json;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    bs58, hash::Hash, instruction::Instruction, pubkey::Pubkey, signature::Signature,
+    signer::Signer, transaction::Transaction,
+};
+
+/// Output format of exported transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Serialized transaction, base58 encoded
+    Base58,
+
+    /// Serialized transaction, base64 encoded
+    Base64,
+
+    /// Fee payer, blockhash and instructions as JSON, for multisig tools that build their own
+    /// transaction
+    Json,
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "base58" => Ok(Self::Base58),
+            "base64" => Ok(Self::Base64),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow::anyhow!(
+                "Unknown export format {s}, expected base58, base64 or json"
+            )),
+        }
+    }
+}
+
+/// Writes transactions, one per line, instead of sending them.
+///
+/// The fee payer does not sign, so it can be a multisig or an offline key. Other signers, such as
+/// generated base keypairs, sign right away and their signatures are kept in the output.
+pub struct TransactionExporter {
+    format: ExportFormat,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl TransactionExporter {
+    /// Appends to `output` if given, otherwise writes to stdout.
+    pub fn new(format: ExportFormat, output: Option<&Path>) -> anyhow::Result<Self> {
+        let out: Box<dyn Write + Send> = match output {
+            Some(path) => Box::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("Failed to open {}", path.display()))?,
+            ),
+            None => Box::new(io::stdout()),
+        };
+
+        Ok(Self {
+            format,
+            out: Mutex::new(out),
+        })
+    }
+
+    pub fn export(
+        &self,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        signers: &[&dyn Signer],
+        blockhash: Hash,
+    ) -> anyhow::Result<()> {
+        let mut tx = Transaction::new_with_payer(instructions, Some(payer));
+        tx.try_partial_sign(signers, blockhash)
+            .context("Failed to sign transaction")?;
+
+        let line = encode_transaction(&tx, self.format)?;
+        let mut out = self.out.lock().expect("export output lock");
+        writeln!(out, "{line}")?;
+        out.flush()?;
+        log::info!("Exported transaction paid by {payer}");
+
+        Ok(())
+    }
+}
+
+/// Encodes a possibly partially signed transaction in `format`.
+pub fn encode_transaction(tx: &Transaction, format: ExportFormat) -> anyhow::Result<String> {
+    match format {
+        ExportFormat::Base58 => Ok(bs58::encode(bincode::serialize(tx)?).into_string()),
+        ExportFormat::Base64 => Ok(STANDARD.encode(bincode::serialize(tx)?)),
+        ExportFormat::Json => {
+            let message = &tx.message;
+            let instructions: Vec<_> = message
+                .instructions
+                .iter()
+                .map(|ix| {
+                    let accounts: Vec<_> = ix
+                        .accounts
+                        .iter()
+                        .map(|index| {
+                            let index = *index as usize;
+                            json!({
+                                "pubkey": message.account_keys[index].to_string(),
+                                "is_signer": message.is_signer(index),
+                                "is_writable": message.is_writable(index),
+                            })
+                        })
+                        .collect();
+                    json!({
+                        "program_id": message.account_keys[ix.program_id_index as usize]
+                            .to_string(),
+                        "accounts": accounts,
+                        "data": STANDARD.encode(&ix.data),
+                    })
+                })
+                .collect();
+            let signatures: Vec<_> = message
+                .account_keys
+                .iter()
+                .zip(tx.signatures.iter())
+                .filter(|(_pubkey, signature)| **signature != Signature::default())
+                .map(|(pubkey, signature)| {
+                    json!({
+                        "pubkey": pubkey.to_string(),
+                        "signature": signature.to_string(),
+                    })
+                })
+                .collect();
+
+            Ok(json!({
+                "fee_payer": message.account_keys[0].to_string(),
+                "recent_blockhash": message.recent_blockhash.to_string(),
+                "instructions": instructions,
+                "signatures": signatures,
+            })
+            .to_string())
+        }
+    }
+}
+
+/// Decodes a serialized transaction, base64 or base58 encoded.
+pub fn decode_transaction(s: &str) -> anyhow::Result<Transaction> {
+    let s = s.trim();
+    // Base58 strings can also be valid base64, so fall back when the bytes are not a transaction
+    if let Some(tx) = STANDARD
+        .decode(s)
+        .ok()
+        .and_then(|bytes| deserialize_transaction(&bytes))
+    {
+        return Ok(tx);
+    }
+
+    let bytes = bs58::decode(s)
+        .into_vec()
+        .context("Transaction is neither base64 nor base58")?;
+    deserialize_transaction(&bytes).context("Failed to deserialize transaction")
+}
+
+/// Deserializes `bytes` if they are exactly one serialized transaction.
+fn deserialize_transaction(bytes: &[u8]) -> Option<Transaction> {
+    let tx: Transaction = bincode::deserialize(bytes).ok()?;
+
+    (bincode::serialize(&tx).ok()? == bytes).then_some(tx)
+}
+
+/// Checks that every signer signed `tx`, then sends and confirms it.
+pub async fn submit_transaction(
+    rpc_client: &RpcClient,
+    tx: &Transaction,
+) -> anyhow::Result<Signature> {
+    let missing: Vec<String> = tx
+        .message
+        .signer_keys()
+        .into_iter()
+        .zip(tx.signatures.iter())
+        .filter(|(_pubkey, signature)| **signature == Signature::default())
+        .map(|(pubkey, _signature)| pubkey.to_string())
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Transaction is missing signatures of {}",
+            missing.join(", ")
+        );
+    }
+    tx.verify()
+        .context("Transaction has an invalid signature")?;
+
+    let sig = rpc_client
+        .send_and_confirm_transaction(tx)
+        .await
+        .context("Failed to send transaction")?;
+    log::info!("Transaction confirmed: {sig}");
+
+    Ok(sig)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{signature::Keypair, system_instruction};
+
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        let multisig = Pubkey::new_unique();
+        let base = Keypair::new();
+        let ix = system_instruction::create_account(&multisig, &base.pubkey(), 1, 0, &multisig);
+        let blockhash = Hash::new_unique();
+
+        let mut tx = Transaction::new_with_payer(&[ix], Some(&multisig));
+        tx.try_partial_sign(&[&base as &dyn Signer], blockhash)
+            .unwrap();
+
+        for format in [ExportFormat::Base58, ExportFormat::Base64] {
+            let decoded = decode_transaction(&encode_transaction(&tx, format).unwrap()).unwrap();
+            assert_eq!(decoded, tx);
+            assert_eq!(decoded.signatures[0], Signature::default());
+        }
+
+        let json: serde_json::Value =
+            serde_json::from_str(&encode_transaction(&tx, ExportFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["fee_payer"], multisig.to_string());
+        assert_eq!(json["recent_blockhash"], blockhash.to_string());
+        assert_eq!(
+            json["instructions"][0]["accounts"][1],
+            json!({
+                "pubkey": base.pubkey().to_string(),
+                "is_signer": true,
+                "is_writable": true,
+            })
+        );
+        assert_eq!(json["signatures"][0]["pubkey"], base.pubkey().to_string());
+        assert!(decode_transaction("not a transaction").is_err());
+    }
+}
@@ -0,0 +1,463 @@
+use std::{path::Path, sync::Arc};
+
+use jito_restaking_core::{
+    ncn_vault_ticket::NcnVaultTicket, operator_vault_ticket::OperatorVaultTicket,
+};
+use jito_vault_client::instructions::{
+    AddDelegationBuilder, CooldownDelegationBuilder, InitializeConfigBuilder,
+    InitializeVaultBuilder, InitializeVaultNcnTicketBuilder,
+    InitializeVaultOperatorDelegationBuilder, SetDepositCapacityBuilder, SetFeesBuilder,
+    WarmupVaultNcnTicketBuilder,
+};
+use jito_vault_core::{
+    vault::Vault, vault_ncn_ticket::VaultNcnTicket,
+    vault_operator_delegation::VaultOperatorDelegation,
+};
+use solana_client::{client_error::ClientError, nonblocking::rpc_client::RpcClient};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::{Signer, SignerError},
+    system_program,
+};
+use thiserror::Error;
+
+use crate::{
+    compute_budget::ComputeUnitEstimator,
+    nonce_pool::NoncePool,
+    program_error::{InstructionFailure, JitoProgram},
+    signer::{resolve_base, sign_transaction, SharedSigner, WriteKeypairError},
+    transaction_export::TransactionExporter,
+};
+
+#[derive(Debug, Error)]
+pub enum VaultAdminHandlerError {
+    #[error("Failed to get latest blockhash: {0}")]
+    Blockhash(#[source] Box<ClientError>),
+
+    #[error("Failed to sign transaction: {0}")]
+    Sign(#[source] SignerError),
+
+    #[error("Failed to export transaction: {0}")]
+    Export(String),
+
+    #[error("Failed to lease a durable nonce: {0}")]
+    Nonce(String),
+
+    #[error("Failed to send transaction: {0}")]
+    SendTransaction(#[source] Box<ClientError>),
+
+    #[error("{failure}")]
+    Instruction {
+        failure: InstructionFailure,
+        #[source]
+        source: Box<ClientError>,
+    },
+
+    #[error(transparent)]
+    WriteKeypair(#[from] WriteKeypairError),
+}
+
+/// Sends Jito vault program admin instructions, using the payer as the vault admin.
+pub struct VaultAdminHandler {
+    rpc_url: String,
+    commitment: CommitmentConfig,
+    payer: SharedSigner,
+    restaking_program_id: Pubkey,
+    vault_program_id: Pubkey,
+
+    /// Exports transactions instead of signing them with the payer and sending them
+    exporter: Option<Arc<TransactionExporter>>,
+
+    /// Durable nonce accounts of the payer used by exported transactions
+    nonces: Option<Arc<NoncePool>>,
+
+    /// Sets the compute-unit limit of every transaction from a simulation
+    compute: Arc<ComputeUnitEstimator>,
+}
+
+impl VaultAdminHandler {
+    pub fn new(
+        rpc_url: &str,
+        commitment: CommitmentConfig,
+        payer: SharedSigner,
+        restaking_program_id: Pubkey,
+        vault_program_id: Pubkey,
+    ) -> Self {
+        Self {
+            rpc_url: rpc_url.to_string(),
+            commitment,
+            payer,
+            restaking_program_id,
+            vault_program_id,
+            exporter: None,
+            nonces: None,
+            compute: Arc::new(ComputeUnitEstimator::default()),
+        }
+    }
+
+    /// Exports the transactions of every operation instead of sending them, which then return no
+    /// signature.
+    pub fn set_exporter(&mut self, exporter: Arc<TransactionExporter>) {
+        self.exporter = Some(exporter);
+    }
+
+    /// Makes exported transactions advance a durable nonce of the payer instead of using a
+    /// recent blockhash, which expires before an offline or multisig signer gets to them.
+    pub fn set_nonce_pool(&mut self, nonces: Arc<NoncePool>) {
+        self.nonces = Some(nonces);
+    }
+
+    pub fn set_compute_unit_estimator(&mut self, compute: Arc<ComputeUnitEstimator>) {
+        self.compute = compute;
+    }
+
+    fn get_rpc_client(&self) -> RpcClient {
+        RpcClient::new_with_commitment(self.rpc_url.clone(), self.commitment)
+    }
+
+    /// Returns the address of the vault `Config` account.
+    pub fn config_address(&self) -> Pubkey {
+        jito_vault_core::config::Config::find_program_address(&self.vault_program_id).0
+    }
+
+    /// Signs `instructions` with the payer and `signers`, then sends and confirms the transaction,
+    /// or exports it if an exporter is set and returns no signature.
+    ///
+    /// The transaction is simulated as it is sent, nonce advance included, to set its compute-unit
+    /// limit. An exported transaction advances a durable nonce leased from the nonce pool, which
+    /// is required then.
+    async fn send_transaction(
+        &self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<Option<Signature>, VaultAdminHandlerError> {
+        let rpc_client = self.get_rpc_client();
+
+        let mut instructions = instructions.to_vec();
+        let signers: Vec<&dyn Signer> = signers
+            .iter()
+            .map(|signer| *signer as &dyn Signer)
+            .collect();
+        if let Some(exporter) = self.exporter.as_ref() {
+            let nonces = self.nonces.as_ref().ok_or_else(|| {
+                VaultAdminHandlerError::Nonce(
+                    "exported transactions need durable nonce accounts".to_string(),
+                )
+            })?;
+            let lease = nonces
+                .acquire(&rpc_client, &self.payer.pubkey())
+                .await
+                .map_err(|e| VaultAdminHandlerError::Nonce(format!("{e:#}")))?
+                .ok_or_else(|| {
+                    VaultAdminHandlerError::Nonce(format!(
+                        "every nonce account of {} is in use",
+                        self.payer.pubkey()
+                    ))
+                })?;
+            instructions.insert(0, lease.advance_instruction());
+            let instructions = self
+                .compute
+                .with_compute_unit_limit(&rpc_client, instructions, &self.payer.pubkey(), None)
+                .await;
+            exporter
+                .export(&instructions, &self.payer.pubkey(), &signers, lease.nonce())
+                .map_err(|e| VaultAdminHandlerError::Export(format!("{e:#}")))?;
+            // The nonce only advances once the transaction is submitted
+            lease.keep();
+            return Ok(None);
+        }
+
+        let instructions = self
+            .compute
+            .with_compute_unit_limit(&rpc_client, instructions, &self.payer.pubkey(), None)
+            .await;
+
+        let blockhash = rpc_client
+            .get_latest_blockhash()
+            .await
+            .map_err(|e| VaultAdminHandlerError::Blockhash(Box::new(e)))?;
+        let tx = sign_transaction(&instructions, self.payer.as_ref(), &signers, blockhash)
+            .map_err(VaultAdminHandlerError::Sign)?;
+
+        match rpc_client.send_and_confirm_transaction(&tx).await {
+            Ok(sig) => {
+                log::info!("Transaction confirmed: {sig}");
+                Ok(Some(sig))
+            }
+            Err(e) => match InstructionFailure::from_client_error(JitoProgram::Vault, &e) {
+                Some(failure) => {
+                    log::error!("{failure}");
+                    Err(VaultAdminHandlerError::Instruction {
+                        failure,
+                        source: Box::new(e),
+                    })
+                }
+                None => {
+                    log::error!("Failed to send transaction: {:?}", e);
+                    Err(VaultAdminHandlerError::SendTransaction(Box::new(e)))
+                }
+            },
+        }
+    }
+
+    /// Initializes the vault `Config` account with the payer as admin and program fee wallet.
+    pub async fn initialize_config(
+        &self,
+        program_fee_bps: u16,
+    ) -> Result<Option<Signature>, VaultAdminHandlerError> {
+        let mut ix_builder = InitializeConfigBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .admin(self.payer.pubkey())
+            .restaking_program(self.restaking_program_id)
+            .program_fee_wallet(self.payer.pubkey())
+            .system_program(system_program::id())
+            .program_fee_bps(program_fee_bps);
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.vault_program_id;
+
+        self.send_transaction(&[ix], &[]).await
+    }
+
+    /// Initializes a new `Vault` backed by `st_mint` with a newly generated VRT mint.
+    ///
+    /// Uses `base` if given, otherwise generates one and writes it to `base_keypair_out` if set.
+    /// Returns the addresses of the `Vault` and the VRT mint, and the transaction signature, `None`
+    /// if it was exported.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn initialize_vault(
+        &self,
+        st_mint: Pubkey,
+        deposit_fee_bps: u16,
+        withdrawal_fee_bps: u16,
+        reward_fee_bps: u16,
+        decimals: u8,
+        base: Option<&Keypair>,
+        base_keypair_out: Option<&Path>,
+    ) -> Result<(Pubkey, Pubkey, Option<Signature>), VaultAdminHandlerError> {
+        let base = resolve_base(base, base_keypair_out)?;
+        let vrt_mint = Keypair::new();
+        let vault = Vault::find_program_address(&self.vault_program_id, &base.pubkey()).0;
+
+        let mut ix_builder = InitializeVaultBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .vault(vault)
+            .vrt_mint(vrt_mint.pubkey())
+            .st_mint(st_mint)
+            .admin(self.payer.pubkey())
+            .base(base.pubkey())
+            .system_program(system_program::id())
+            .deposit_fee_bps(deposit_fee_bps)
+            .withdrawal_fee_bps(withdrawal_fee_bps)
+            .reward_fee_bps(reward_fee_bps)
+            .decimals(decimals);
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.vault_program_id;
+
+        let sig = self.send_transaction(&[ix], &[&vrt_mint, &base]).await?;
+
+        Ok((vault, vrt_mint.pubkey(), sig))
+    }
+
+    /// Initializes the `VaultOperatorDelegation` between `vault` and `operator`.
+    ///
+    /// The `OperatorVaultTicket` for the pair must already exist in the restaking program.
+    /// Returns the address of the `VaultOperatorDelegation` and the transaction signature, `None`
+    /// if it was exported.
+    pub async fn initialize_vault_operator_delegation(
+        &self,
+        vault: Pubkey,
+        operator: Pubkey,
+    ) -> Result<(Pubkey, Option<Signature>), VaultAdminHandlerError> {
+        let operator_vault_ticket = OperatorVaultTicket::find_program_address(
+            &self.restaking_program_id,
+            &operator,
+            &vault,
+        )
+        .0;
+        let vault_operator_delegation = VaultOperatorDelegation::find_program_address(
+            &self.vault_program_id,
+            &vault,
+            &operator,
+        )
+        .0;
+
+        let mut ix_builder = InitializeVaultOperatorDelegationBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .vault(vault)
+            .operator(operator)
+            .operator_vault_ticket(operator_vault_ticket)
+            .vault_operator_delegation(vault_operator_delegation)
+            .admin(self.payer.pubkey())
+            .payer(self.payer.pubkey())
+            .system_program(system_program::id());
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.vault_program_id;
+
+        let sig = self.send_transaction(&[ix], &[]).await?;
+
+        Ok((vault_operator_delegation, sig))
+    }
+
+    /// Initializes the `VaultNcnTicket` between `vault` and `ncn`.
+    ///
+    /// The `NcnVaultTicket` for the pair must already be active in the restaking program.
+    /// Returns the address of the `VaultNcnTicket` and the transaction signature, `None` if it was
+    /// exported.
+    pub async fn initialize_vault_ncn_ticket(
+        &self,
+        vault: Pubkey,
+        ncn: Pubkey,
+    ) -> Result<(Pubkey, Option<Signature>), VaultAdminHandlerError> {
+        let ncn_vault_ticket =
+            NcnVaultTicket::find_program_address(&self.restaking_program_id, &ncn, &vault).0;
+        let vault_ncn_ticket =
+            VaultNcnTicket::find_program_address(&self.vault_program_id, &vault, &ncn).0;
+
+        let mut ix_builder = InitializeVaultNcnTicketBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .vault(vault)
+            .ncn(ncn)
+            .ncn_vault_ticket(ncn_vault_ticket)
+            .vault_ncn_ticket(vault_ncn_ticket)
+            .admin(self.payer.pubkey())
+            .payer(self.payer.pubkey())
+            .system_program(system_program::id());
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.vault_program_id;
+
+        let sig = self.send_transaction(&[ix], &[]).await?;
+
+        Ok((vault_ncn_ticket, sig))
+    }
+
+    /// Warms up the `VaultNcnTicket` between `vault` and `ncn`.
+    pub async fn warmup_vault_ncn_ticket(
+        &self,
+        vault: Pubkey,
+        ncn: Pubkey,
+    ) -> Result<Option<Signature>, VaultAdminHandlerError> {
+        let vault_ncn_ticket =
+            VaultNcnTicket::find_program_address(&self.vault_program_id, &vault, &ncn).0;
+
+        let mut ix_builder = WarmupVaultNcnTicketBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .vault(vault)
+            .ncn(ncn)
+            .vault_ncn_ticket(vault_ncn_ticket)
+            .admin(self.payer.pubkey());
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.vault_program_id;
+
+        self.send_transaction(&[ix], &[]).await
+    }
+
+    /// Delegates `amount` of the vault's supported token to `operator`.
+    pub async fn add_delegation(
+        &self,
+        vault: Pubkey,
+        operator: Pubkey,
+        amount: u64,
+    ) -> Result<Option<Signature>, VaultAdminHandlerError> {
+        let vault_operator_delegation = VaultOperatorDelegation::find_program_address(
+            &self.vault_program_id,
+            &vault,
+            &operator,
+        )
+        .0;
+
+        let mut ix_builder = AddDelegationBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .vault(vault)
+            .operator(operator)
+            .vault_operator_delegation(vault_operator_delegation)
+            .admin(self.payer.pubkey())
+            .amount(amount);
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.vault_program_id;
+
+        self.send_transaction(&[ix], &[]).await
+    }
+
+    /// Starts cooling down `amount` of the delegation from `vault` to `operator`.
+    pub async fn cooldown_delegation(
+        &self,
+        vault: Pubkey,
+        operator: Pubkey,
+        amount: u64,
+    ) -> Result<Option<Signature>, VaultAdminHandlerError> {
+        let vault_operator_delegation = VaultOperatorDelegation::find_program_address(
+            &self.vault_program_id,
+            &vault,
+            &operator,
+        )
+        .0;
+
+        let mut ix_builder = CooldownDelegationBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .vault(vault)
+            .operator(operator)
+            .vault_operator_delegation(vault_operator_delegation)
+            .admin(self.payer.pubkey())
+            .amount(amount);
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.vault_program_id;
+
+        self.send_transaction(&[ix], &[]).await
+    }
+
+    /// Sets the maximum amount of supported token the vault accepts.
+    pub async fn set_deposit_capacity(
+        &self,
+        vault: Pubkey,
+        amount: u64,
+    ) -> Result<Option<Signature>, VaultAdminHandlerError> {
+        let mut ix_builder = SetDepositCapacityBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .vault(vault)
+            .admin(self.payer.pubkey())
+            .amount(amount);
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.vault_program_id;
+
+        self.send_transaction(&[ix], &[]).await
+    }
+
+    /// Sets the vault fees in basis points, leaving the fees passed as `None` unchanged.
+    pub async fn set_fees(
+        &self,
+        vault: Pubkey,
+        deposit_fee_bps: Option<u16>,
+        withdrawal_fee_bps: Option<u16>,
+        reward_fee_bps: Option<u16>,
+    ) -> Result<Option<Signature>, VaultAdminHandlerError> {
+        let mut ix_builder = SetFeesBuilder::new();
+        ix_builder
+            .config(self.config_address())
+            .vault(vault)
+            .admin(self.payer.pubkey());
+        if let Some(deposit_fee_bps) = deposit_fee_bps {
+            ix_builder.deposit_fee_bps(deposit_fee_bps);
+        }
+        if let Some(withdrawal_fee_bps) = withdrawal_fee_bps {
+            ix_builder.withdrawal_fee_bps(withdrawal_fee_bps);
+        }
+        if let Some(reward_fee_bps) = reward_fee_bps {
+            ix_builder.reward_fee_bps(reward_fee_bps);
+        }
+        let mut ix = ix_builder.instruction();
+        ix.program_id = self.vault_program_id;
+
+        self.send_transaction(&[ix], &[]).await
+    }
+}
@@ -1,34 +1,228 @@
 use std::collections::HashMap;
 
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use jito_bytemuck::{AccountDeserialize, Discriminator};
 use jito_vault_core::{
     vault::Vault, vault_operator_delegation::VaultOperatorDelegation,
     vault_update_state_tracker::VaultUpdateStateTracker,
 };
-use solana_account_decoder::UiAccountEncoding;
+use serde_json::json;
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
 use solana_client::{
     nonblocking::rpc_client::RpcClient,
     rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
-    rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
+    rpc_filter::RpcFilterType,
+    rpc_request::RpcRequest,
+    rpc_response::{OptionalContext, RpcKeyedAccount},
 };
-use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use solana_sdk::{
+    account::Account, clock::Slot, commitment_config::CommitmentConfig, pubkey::Pubkey,
+};
+
+use crate::account_layout::{
+    discriminator_filter, pad_delegation, pubkey_filter, u64_filter, DELEGATION_READ_LEN,
+    DELEGATION_VAULT_OFFSET, HEADER_LEN, TRACKER_NCN_EPOCH_OFFSET,
+};
+
+/// How many times [`VaultProgramHandler::get_snapshot`] reads the accounts before giving up.
+const SNAPSHOT_ATTEMPTS: usize = 5;
+
+/// Vaults, delegations and trackers of the vault program, read at the same slot.
+#[derive(Debug, Default)]
+pub struct ProgramSnapshot {
+    /// Slot the accounts were read at
+    pub slot: Slot,
+    pub vaults: HashMap<Pubkey, Vault>,
+    pub delegations: Vec<(Pubkey, VaultOperatorDelegation)>,
+
+    /// Keyed by vault
+    pub trackers: HashMap<Pubkey, (Pubkey, VaultUpdateStateTracker)>,
+}
+
+impl ProgramSnapshot {
+    /// Returns why the accounts cannot all belong to the same slot, if they can't.
+    ///
+    /// Every delegation and tracker must reference a known vault, and every vault must have as
+    /// many delegations as its `operator_count`.
+    pub fn inconsistency(&self) -> Option<String> {
+        let mut delegation_counts: HashMap<Pubkey, u64> = HashMap::new();
+        for (pubkey, delegation) in self.delegations.iter() {
+            if !self.vaults.contains_key(&delegation.vault) {
+                return Some(format!(
+                    "VaultOperatorDelegation {pubkey} references unknown vault {}",
+                    delegation.vault
+                ));
+            }
+            *delegation_counts.entry(delegation.vault).or_default() += 1;
+        }
+
+        for (vault, (pubkey, _tracker)) in self.trackers.iter() {
+            if !self.vaults.contains_key(vault) {
+                return Some(format!(
+                    "VaultUpdateStateTracker {pubkey} references unknown vault {vault}"
+                ));
+            }
+        }
+
+        for (pubkey, vault) in self.vaults.iter() {
+            let count = delegation_counts.get(pubkey).copied().unwrap_or_default();
+            if count != vault.operator_count() {
+                return Some(format!(
+                    "Vault {pubkey} has operator count {} but {count} delegations",
+                    vault.operator_count()
+                ));
+            }
+        }
+
+        None
+    }
+}
 
 pub struct VaultProgramHandler {
     rpc_url: String,
+    commitment: CommitmentConfig,
     vault_program_id: Pubkey,
 }
 
 impl VaultProgramHandler {
-    pub async fn new(rpc_url: &str, vault_program_id: Pubkey) -> anyhow::Result<Self> {
+    pub async fn new(
+        rpc_url: &str,
+        commitment: CommitmentConfig,
+        vault_program_id: Pubkey,
+    ) -> anyhow::Result<Self> {
         Ok(Self {
             rpc_url: rpc_url.to_string(),
+            commitment,
             vault_program_id,
         })
     }
 
     fn get_rpc_client(&self) -> RpcClient {
-        RpcClient::new_with_commitment(self.rpc_url.clone(), CommitmentConfig::confirmed())
+        RpcClient::new_with_commitment(self.rpc_url.clone(), self.commitment)
+    }
+
+    /// Runs `getProgramAccounts` on the vault program and returns the slot it was served at.
+    ///
+    /// `get_program_accounts_with_config` drops the response context, so the request is sent
+    /// directly.
+    async fn get_program_accounts(
+        &self,
+        filters: Vec<RpcFilterType>,
+        data_slice: Option<UiDataSliceConfig>,
+        min_context_slot: Option<Slot>,
+    ) -> anyhow::Result<(Slot, Vec<(Pubkey, Account)>)> {
+        let rpc_client = self.get_rpc_client();
+        let config = RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                data_slice,
+                commitment: Some(self.commitment),
+                min_context_slot,
+            },
+            with_context: Some(true),
+        };
+
+        let response = rpc_client
+            .send::<OptionalContext<Vec<RpcKeyedAccount>>>(
+                RpcRequest::GetProgramAccounts,
+                json!([self.vault_program_id.to_string(), config]),
+            )
+            .await?;
+        let (slot, keyed_accounts) = match response {
+            OptionalContext::Context(response) => (response.context.slot, response.value),
+            OptionalContext::NoContext(_) => {
+                return Err(anyhow!(
+                    "RPC did not return the context of getProgramAccounts"
+                ));
+            }
+        };
+
+        let accounts = keyed_accounts
+            .into_iter()
+            .filter_map(|RpcKeyedAccount { pubkey, account }| {
+                Some((pubkey.parse().ok()?, account.decode()?))
+            })
+            .collect();
+
+        Ok((slot, accounts))
+    }
+
+    /// Reads the vaults, delegations and trackers at the same slot.
+    ///
+    /// Only the trackers of the current and the previous NCN epoch are read, filtered by their
+    /// `ncn_epoch` on the RPC side. A tracker left open longer than that is not in the snapshot.
+    ///
+    /// The NCN epoch of the trackers is the one of the latest slot the reads were served at.
+    /// When that differs from the epoch the trackers were filtered by, or when the accounts
+    /// disagree, they are read again no earlier than that slot.
+    pub async fn get_snapshot(&self, epoch_length: u64) -> anyhow::Result<ProgramSnapshot> {
+        let rpc_client = self.get_rpc_client();
+        let mut min_context_slot = rpc_client.get_slot().await.context("failed to get slot")?;
+
+        for attempt in 1..=SNAPSHOT_ATTEMPTS {
+            let ncn_epoch = min_context_slot / epoch_length;
+            let (vaults, delegations, trackers, previous_trackers) = tokio::try_join!(
+                self.get_vaults_with_context(Some(min_context_slot)),
+                self.get_vault_operator_delegations_with_filters(
+                    Vec::new(),
+                    Some(min_context_slot)
+                ),
+                self.get_update_state_trackers_with_filters(
+                    vec![u64_filter(TRACKER_NCN_EPOCH_OFFSET, ncn_epoch)],
+                    Some(min_context_slot)
+                ),
+                self.get_update_state_trackers_with_filters(
+                    vec![u64_filter(
+                        TRACKER_NCN_EPOCH_OFFSET,
+                        ncn_epoch.saturating_sub(1)
+                    )],
+                    Some(min_context_slot)
+                ),
+            )?;
+            let (vaults_slot, vaults) = vaults;
+            let (delegations_slot, delegations) = delegations;
+            let (trackers_slot, mut trackers) = trackers;
+            let (previous_trackers_slot, previous_trackers) = previous_trackers;
+            for (vault, tracker) in previous_trackers {
+                trackers.entry(vault).or_insert(tracker);
+            }
+
+            let slot = vaults_slot
+                .max(delegations_slot)
+                .max(trackers_slot)
+                .max(previous_trackers_slot);
+            if slot / epoch_length != ncn_epoch {
+                log::info!(
+                    "Snapshot attempt {attempt} was served at slot {slot} after NCN epoch \
+                     {ncn_epoch} ended"
+                );
+                min_context_slot = slot;
+                continue;
+            }
+
+            let snapshot = ProgramSnapshot {
+                slot,
+                vaults,
+                delegations,
+                trackers,
+            };
+            match snapshot.inconsistency() {
+                None => return Ok(snapshot),
+                Some(reason) => {
+                    log::warn!(
+                        "Snapshot attempt {attempt} read at slots {vaults_slot}, \
+                         {delegations_slot}, {trackers_slot} and {previous_trackers_slot} \
+                         disagrees: {reason}"
+                    );
+                    min_context_slot = snapshot.slot;
+                }
+            }
+        }
+
+        Err(anyhow!(
+            "Failed to read a consistent vault program snapshot in {SNAPSHOT_ATTEMPTS} attempts"
+        ))
     }
 
     pub async fn get_config(&self) -> jito_vault_core::config::Config {
@@ -46,6 +240,28 @@ impl VaultProgramHandler {
         *config
     }
 
+    /// Returns the balance of `pubkey` in lamports.
+    pub async fn get_balance(&self, pubkey: &Pubkey) -> anyhow::Result<u64> {
+        let rpc_client = self.get_rpc_client();
+
+        rpc_client
+            .get_balance(pubkey)
+            .await
+            .with_context(|| format!("Failed to get balance of {pubkey}"))
+    }
+
+    /// Returns the rent a `VaultUpdateStateTracker` account holds while it is open.
+    pub async fn get_tracker_rent(&self) -> anyhow::Result<u64> {
+        let rpc_client = self.get_rpc_client();
+
+        rpc_client
+            .get_minimum_balance_for_rent_exemption(
+                HEADER_LEN + std::mem::size_of::<VaultUpdateStateTracker>(),
+            )
+            .await
+            .context("Failed to get VaultUpdateStateTracker rent")
+    }
+
     pub async fn get_current_epoch(&self) -> anyhow::Result<u64> {
         let rpc_client = self.get_rpc_client();
 
@@ -57,29 +273,69 @@ impl VaultProgramHandler {
         Ok(epoch)
     }
 
-    pub async fn get_vaults(&self) -> anyhow::Result<HashMap<Pubkey, Vault>> {
+    /// Fetches and deserializes a single `Vault` account.
+    pub async fn get_vault(&self, vault: &Pubkey) -> anyhow::Result<Vault> {
         let rpc_client = self.get_rpc_client();
-        let accounts = rpc_client
-            .get_program_accounts_with_config(
-                &self.vault_program_id,
-                RpcProgramAccountsConfig {
-                    filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new(
-                        0,
-                        MemcmpEncodedBytes::Bytes(vec![Vault::DISCRIMINATOR]),
-                    ))]),
-                    account_config: RpcAccountInfoConfig {
-                        encoding: Some(UiAccountEncoding::Base64),
-                        data_slice: None,
-                        commitment: None,
-                        min_context_slot: None,
-                    },
-                    with_context: None,
-                },
+
+        let account = rpc_client
+            .get_account(vault)
+            .await
+            .with_context(|| format!("Failed to get Vault account: {vault}"))?;
+        let vault = Vault::try_from_slice_unchecked(&account.data)
+            .with_context(|| format!("Failed to deserialize Vault: {vault}"))?;
+
+        Ok(*vault)
+    }
+
+    /// Fetches the `VaultUpdateStateTracker` of `vault` for `ncn_epoch` by its PDA.
+    ///
+    /// Returns `None` if the tracker has not been initialized (or has already been closed).
+    pub async fn get_update_state_tracker(
+        &self,
+        vault: &Pubkey,
+        ncn_epoch: u64,
+    ) -> anyhow::Result<Option<(Pubkey, VaultUpdateStateTracker)>> {
+        let rpc_client = self.get_rpc_client();
+
+        let tracker_pubkey =
+            VaultUpdateStateTracker::find_program_address(&self.vault_program_id, vault, ncn_epoch)
+                .0;
+        let account = rpc_client
+            .get_account_with_commitment(&tracker_pubkey, rpc_client.commitment())
+            .await
+            .with_context(|| format!("Failed to get VaultUpdateStateTracker: {tracker_pubkey}"))?
+            .value;
+
+        match account {
+            Some(account) => {
+                let tracker = VaultUpdateStateTracker::try_from_slice_unchecked(&account.data)
+                    .with_context(|| {
+                        format!("Failed to deserialize VaultUpdateStateTracker: {tracker_pubkey}")
+                    })?;
+                Ok(Some((tracker_pubkey, *tracker)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub async fn get_vaults(&self) -> anyhow::Result<HashMap<Pubkey, Vault>> {
+        Ok(self.get_vaults_with_context(None).await?.1)
+    }
+
+    async fn get_vaults_with_context(
+        &self,
+        min_context_slot: Option<Slot>,
+    ) -> anyhow::Result<(Slot, HashMap<Pubkey, Vault>)> {
+        let (slot, accounts) = self
+            .get_program_accounts(
+                vec![discriminator_filter(Vault::DISCRIMINATOR)],
+                None,
+                min_context_slot,
             )
             .await
             .with_context(|| {
-                log::error!("Error failed to get VaultUpdateStateTracker");
-                "Failed to get VaultUpdateStateTracker accounts".to_string()
+                log::error!("Error failed to get Vault");
+                "Failed to get Vault accounts".to_string()
             })?;
 
         let vaults: Vec<(Pubkey, Vault)> = accounts
@@ -92,7 +348,7 @@ impl VaultProgramHandler {
             })
             .collect();
 
-        Ok(HashMap::from_iter(vaults))
+        Ok((slot, HashMap::from_iter(vaults)))
     }
 
     /// Retrieves all existing `VaultOperatorDelegation` accounts associated with the program.
@@ -106,56 +362,73 @@ impl VaultProgramHandler {
     pub async fn get_vault_operator_delegations(
         &self,
     ) -> anyhow::Result<Vec<(Pubkey, VaultOperatorDelegation)>> {
-        let rpc_client = self.get_rpc_client();
-        let accounts = rpc_client
-            .get_program_accounts_with_config(
-                &self.vault_program_id,
-                RpcProgramAccountsConfig {
-                    filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new(
-                        0,
-                        MemcmpEncodedBytes::Bytes(vec![VaultOperatorDelegation::DISCRIMINATOR]),
-                    ))]),
-                    account_config: RpcAccountInfoConfig {
-                        encoding: Some(UiAccountEncoding::Base64),
-                        ..RpcAccountInfoConfig::default()
-                    },
-                    ..RpcProgramAccountsConfig::default()
-                },
+        Ok(self
+            .get_vault_operator_delegations_with_filters(Vec::new(), None)
+            .await?
+            .1)
+    }
+
+    /// Retrieves the `VaultOperatorDelegation` accounts of a single vault.
+    pub async fn get_vault_operator_delegations_by_vault(
+        &self,
+        vault: &Pubkey,
+    ) -> anyhow::Result<Vec<(Pubkey, VaultOperatorDelegation)>> {
+        Ok(self
+            .get_vault_operator_delegations_with_filters(
+                vec![pubkey_filter(DELEGATION_VAULT_OFFSET, vault)],
+                None,
             )
+            .await?
+            .1)
+    }
+
+    async fn get_vault_operator_delegations_with_filters(
+        &self,
+        filters: Vec<RpcFilterType>,
+        min_context_slot: Option<Slot>,
+    ) -> anyhow::Result<(Slot, Vec<(Pubkey, VaultOperatorDelegation)>)> {
+        let mut all_filters = vec![discriminator_filter(VaultOperatorDelegation::DISCRIMINATOR)];
+        all_filters.extend(filters);
+
+        // The bump and the reserved bytes are not needed, the rest is zero-padded back
+        let data_slice = UiDataSliceConfig {
+            offset: 0,
+            length: DELEGATION_READ_LEN,
+        };
+        let (slot, accounts) = self
+            .get_program_accounts(all_filters, Some(data_slice), min_context_slot)
             .await?;
 
         let delegations: Vec<(Pubkey, VaultOperatorDelegation)> = accounts
             .into_iter()
             .filter_map(|(pubkey, acc)| {
-                VaultOperatorDelegation::try_from_slice_unchecked(&acc.data)
+                VaultOperatorDelegation::try_from_slice_unchecked(&pad_delegation(acc.data))
                     .map_or(None, |v| Some((pubkey, *v)))
             })
             .collect();
 
-        Ok(delegations)
+        Ok((slot, delegations))
     }
 
     pub async fn get_update_state_trackers(
         &self,
     ) -> anyhow::Result<HashMap<Pubkey, (Pubkey, VaultUpdateStateTracker)>> {
-        let rpc_client = self.get_rpc_client();
-        let accounts = rpc_client
-            .get_program_accounts_with_config(
-                &self.vault_program_id,
-                RpcProgramAccountsConfig {
-                    filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new(
-                        0,
-                        MemcmpEncodedBytes::Bytes(vec![VaultUpdateStateTracker::DISCRIMINATOR]),
-                    ))]),
-                    account_config: RpcAccountInfoConfig {
-                        encoding: Some(UiAccountEncoding::Base64),
-                        data_slice: None,
-                        commitment: None,
-                        min_context_slot: None,
-                    },
-                    with_context: None,
-                },
-            )
+        Ok(self
+            .get_update_state_trackers_with_filters(Vec::new(), None)
+            .await?
+            .1)
+    }
+
+    async fn get_update_state_trackers_with_filters(
+        &self,
+        filters: Vec<RpcFilterType>,
+        min_context_slot: Option<Slot>,
+    ) -> anyhow::Result<(Slot, HashMap<Pubkey, (Pubkey, VaultUpdateStateTracker)>)> {
+        let mut all_filters = vec![discriminator_filter(VaultUpdateStateTracker::DISCRIMINATOR)];
+        all_filters.extend(filters);
+
+        let (slot, accounts) = self
+            .get_program_accounts(all_filters, None, min_context_slot)
             .await
             .with_context(|| {
                 log::error!("Error failed to get VaultUpdateStateTracker");
@@ -175,11 +448,73 @@ impl VaultProgramHandler {
             })
             .collect();
 
-        let mut map = HashMap::new();
+        // A vault can still have the tracker of an earlier epoch, keep the latest one
+        let mut map: HashMap<Pubkey, (Pubkey, VaultUpdateStateTracker)> = HashMap::new();
         for tracker in trackers {
-            map.entry(tracker.1.vault).or_insert((tracker.0, tracker.1));
+            match map.get(&tracker.1.vault) {
+                Some(existing) if existing.1.ncn_epoch() >= tracker.1.ncn_epoch() => {}
+                _ => {
+                    map.insert(tracker.1.vault, tracker);
+                }
+            }
+        }
+
+        Ok((slot, map))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault(operator_count: u64) -> Vault {
+        let mut vault = Vault::new(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            0,
+            Pubkey::new_unique(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        )
+        .unwrap();
+        for _ in 0..operator_count {
+            vault.increment_operator_count().unwrap();
         }
+        vault
+    }
+
+    #[test]
+    fn test_snapshot_inconsistency() {
+        let vault_pubkey = Pubkey::new_unique();
+        let delegation = (
+            Pubkey::new_unique(),
+            VaultOperatorDelegation::new(vault_pubkey, Pubkey::new_unique(), 0, 0, 0),
+        );
+        let tracker = (
+            Pubkey::new_unique(),
+            VaultUpdateStateTracker::new(vault_pubkey, 1, 0),
+        );
+
+        let mut snapshot = ProgramSnapshot {
+            slot: 100,
+            vaults: HashMap::from([(vault_pubkey, vault(1))]),
+            delegations: vec![delegation],
+            trackers: HashMap::from([(vault_pubkey, tracker)]),
+        };
+        assert!(snapshot.inconsistency().is_none());
+
+        // Delegation read after the vault incremented its operator count
+        snapshot.vaults.insert(vault_pubkey, vault(2));
+        assert!(snapshot.inconsistency().is_some());
 
-        Ok(map)
+        // Vault not returned at all
+        snapshot.vaults.clear();
+        snapshot.delegations.clear();
+        assert!(snapshot.inconsistency().is_some());
     }
 }
@@ -1,5 +1,6 @@
-use std::ops::Rem;
+use std::{cmp::Ordering, ops::Rem, sync::Arc};
 
+use anyhow::Context;
 use jito_bytemuck::AccountDeserialize;
 use jito_vault_client::{
     instructions::{
@@ -14,31 +15,103 @@ use jito_vault_core::{
 };
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
-    commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Keypair, signer::Signer,
-    system_program, transaction::Transaction,
+    address_lookup_table::AddressLookupTableAccount,
+    commitment_config::CommitmentConfig,
+    compute_budget::ComputeBudgetInstruction,
+    instruction::Instruction,
+    message::{v0, VersionedMessage},
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::Signer,
+    system_program,
+    transaction::VersionedTransaction,
 };
+use tokio::time::Instant;
 
-pub struct VaultStateManager<'a> {
+use crate::{
+    compute_budget::ComputeUnitEstimator,
+    history::{CrankHistory, TransactionRecord},
+    lookup_table::{ensure_lookup_table, vault_addresses},
+    nonce_pool::NoncePool,
+    payer_pool::PayerPool,
+    priority_fee::PriorityFeeEscalator,
+    program_error::{check_send_result, JitoProgram},
+    signer::{sign_transaction, SharedSigner},
+    transaction_export::TransactionExporter,
+};
+
+/// Next step to bring a vault up to date, picked by [`VaultStateManager::next_action`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VaultAction {
+    /// The vault was fully updated in the current epoch
+    UpToDate,
+
+    /// Initialize the VaultUpdateStateTracker of the current epoch
+    Initialize,
+
+    /// Crank the delegations into the tracker of the current epoch
+    Crank,
+
+    /// Close the fully cranked tracker of the current epoch
+    Close,
+
+    /// Close a tracker left over from an earlier epoch, which does not need to be cranked
+    CloseStale,
+
+    /// No step can be taken on the accounts at hand
+    Skip(&'static str),
+}
+
+pub struct VaultStateManager {
     /// RPC URL
     rpc_url: String,
 
+    /// Commitment level of every RPC request
+    commitment: CommitmentConfig,
+
     /// Jito Vault Program ID
     vault_program_id: Pubkey,
 
-    /// The payer to send tx
-    payer: &'a Keypair,
+    payers: Arc<PayerPool>,
+
+    /// The payer to send tx, assigned from `payers`
+    payer: SharedSigner,
 
     config_pubkey: Pubkey,
     vault: (Pubkey, Vault),
     tracker: Option<(Pubkey, VaultUpdateStateTracker)>,
     operator_delegations: Option<Vec<(Pubkey, VaultOperatorDelegation)>>,
+    history: Option<Arc<CrankHistory>>,
+
+    /// Skip initializations, which pay the tracker rent
+    initialize_paused: bool,
+
+    /// Why the spending budget can not cover another update of the vault in this epoch, which
+    /// holds back initializations
+    budget_exhausted: Option<&'static str>,
+
+    /// Exports transactions instead of signing them with the payer and sending them
+    exporter: Option<Arc<TransactionExporter>>,
+
+    /// Durable nonce accounts used instead of recent blockhashes
+    nonces: Option<Arc<NoncePool>>,
+
+    /// Cranks sent per v0 transaction, 1 sends legacy transactions without a lookup table
+    crank_batch_size: usize,
+
+    /// Sets the compute-unit limit of every transaction from a simulation
+    compute: Arc<ComputeUnitEstimator>,
+
+    /// Sets the priority fee of every transaction, none without it
+    priority_fees: Option<Arc<PriorityFeeEscalator>>,
 }
 
-impl<'a> VaultStateManager<'a> {
+impl VaultStateManager {
     pub fn new(
         rpc_url: &str,
+        commitment: CommitmentConfig,
         vault_program_id: Pubkey,
-        payer: &'a Keypair,
+        payers: Arc<PayerPool>,
         vault: (Pubkey, Vault),
     ) -> Self {
         let config_pubkey =
@@ -46,12 +119,22 @@ impl<'a> VaultStateManager<'a> {
 
         Self {
             rpc_url: rpc_url.to_string(),
+            commitment,
             vault_program_id,
-            payer,
+            payer: payers.assign(&vault.0),
+            payers,
             config_pubkey,
             vault,
             tracker: None,
             operator_delegations: None,
+            history: None,
+            initialize_paused: false,
+            budget_exhausted: None,
+            exporter: None,
+            nonces: None,
+            crank_batch_size: 1,
+            compute: Arc::new(ComputeUnitEstimator::default()),
+            priority_fees: None,
         }
     }
 
@@ -65,6 +148,214 @@ impl<'a> VaultStateManager<'a> {
         last_update_epoch < current_epoch
     }
 
+    /// Returns the last NCN epoch if it ended without the vault being fully updated.
+    pub fn missed_epoch(&self, current_epoch: u64, epoch_length: u64) -> Option<u64> {
+        let last_update_epoch = self.vault.1.last_full_state_update_slot() / epoch_length;
+
+        (last_update_epoch + 1 < current_epoch).then(|| current_epoch - 1)
+    }
+
+    /// Returns true if every operator delegation has been cranked into the tracker.
+    ///
+    /// Delegations are cranked starting from index `ncn_epoch % operator_count`, so the tracker is
+    /// complete once `last_updated_index` is the index right before that one.
+    pub fn is_cranked(&self) -> bool {
+        match self.tracker {
+            Some(tracker) => {
+                let operator_count = self.vault.1.operator_count();
+                if operator_count == 0 {
+                    return true;
+                }
+
+                let start_index = tracker.1.ncn_epoch().rem(operator_count);
+                let last_index = (start_index + operator_count - 1).rem(operator_count);
+
+                tracker.1.last_updated_index() == last_index
+            }
+            None => false,
+        }
+    }
+
+    /// Picks the next step from the tracker's NCN epoch, the vault's last full state update and
+    /// the delegations.
+    pub fn next_action(&self, current_epoch: u64, epoch_length: u64) -> VaultAction {
+        let Some(tracker) = self.tracker else {
+            return if !self.is_update_needed(current_epoch, epoch_length) {
+                VaultAction::UpToDate
+            } else if let Some(reason) = self.budget_exhausted {
+                VaultAction::Skip(reason)
+            } else if self.initialize_paused {
+                VaultAction::Skip("initializations are paused")
+            } else {
+                VaultAction::Initialize
+            };
+        };
+
+        match tracker.1.ncn_epoch().cmp(&current_epoch) {
+            Ordering::Less => VaultAction::CloseStale,
+            Ordering::Greater => VaultAction::Skip("tracker belongs to a future epoch"),
+            Ordering::Equal => {
+                if !self.is_update_needed(current_epoch, epoch_length) {
+                    // Closing updates the vault, so the tracker has to be stale data
+                    VaultAction::Skip("vault already updated in the tracker's epoch")
+                } else if self.is_cranked() {
+                    VaultAction::Close
+                } else if self
+                    .operator_delegations
+                    .as_ref()
+                    .map_or(true, |delegations| {
+                        delegations.len() as u64 != self.vault.1.operator_count()
+                    })
+                {
+                    VaultAction::Skip("delegations do not match the operator count")
+                } else {
+                    VaultAction::Crank
+                }
+            }
+        }
+    }
+
+    /// Runs the step returned by [`Self::next_action`] and returns it.
+    ///
+    /// A stale tracker is closed and the tracker of the current epoch initialized right away. Every
+    /// step is recorded in the history, if one is set, under the NCN epoch of its tracker, so the
+    /// close of a stale tracker and the initialization that follows are recorded apart.
+    pub async fn step(&self, current_epoch: u64, epoch_length: u64) -> anyhow::Result<VaultAction> {
+        let action = self.next_action(current_epoch, epoch_length);
+        let ncn_epoch = match (action, self.tracker) {
+            (VaultAction::Initialize, _) | (_, None) => current_epoch,
+            (_, Some(tracker)) => tracker.1.ncn_epoch(),
+        };
+        self.run(action, ncn_epoch, current_epoch).await?;
+
+        if action == VaultAction::CloseStale
+            && !self.initialize_paused
+            && self.budget_exhausted.is_none()
+            && self.is_update_needed(current_epoch, epoch_length)
+        {
+            self.run(VaultAction::Initialize, current_epoch, current_epoch)
+                .await?;
+        }
+
+        Ok(action)
+    }
+
+    /// Executes `action` and records it in the history under `ncn_epoch`.
+    pub async fn run(
+        &self,
+        action: VaultAction,
+        ncn_epoch: u64,
+        current_epoch: u64,
+    ) -> anyhow::Result<()> {
+        let record = match (self.history.as_ref(), action) {
+            (None, _) | (_, VaultAction::UpToDate | VaultAction::Skip(_)) => None,
+            (Some(history), _) => {
+                match history.plan(&self.vault.0, ncn_epoch, &format!("{action:?}")) {
+                    Ok(id) => Some((history, id)),
+                    Err(e) => {
+                        log::error!(
+                            "Failed to record {action:?} of vault {}: {e:?}",
+                            self.vault.0
+                        );
+                        None
+                    }
+                }
+            }
+        };
+
+        let started = Instant::now();
+        let result = self.execute(action, current_epoch).await;
+
+        if let Some((history, id)) = record {
+            let rpc_client = self.get_rpc_client();
+            let mut transactions = Vec::new();
+            for signature in result.as_deref().unwrap_or_default() {
+                transactions.push(TransactionRecord::fetch(&rpc_client, *signature).await);
+            }
+            let error = result.as_ref().err().map(|e| format!("{e:#}"));
+            if let Err(e) = history.finish(id, started.elapsed(), &transactions, error.as_deref()) {
+                log::error!(
+                    "Failed to record {action:?} of vault {}: {e:?}",
+                    self.vault.0
+                );
+            }
+        }
+
+        result.map(|_signatures| ())
+    }
+
+    async fn execute(
+        &self,
+        action: VaultAction,
+        current_epoch: u64,
+    ) -> anyhow::Result<Vec<Signature>> {
+        match action {
+            VaultAction::UpToDate => Ok(Vec::new()),
+            VaultAction::Initialize => self.initialize(current_epoch).await,
+            VaultAction::Crank => self.crank().await,
+            VaultAction::Close | VaultAction::CloseStale => self.close().await,
+            VaultAction::Skip(reason) => {
+                log::warn!("Skipping vault {}: {reason}", self.vault.0);
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    /// Pauses or resumes initializations, closing stale trackers still reclaims their rent.
+    pub fn set_initialize_paused(&mut self, paused: bool) {
+        self.initialize_paused = paused;
+    }
+
+    /// Holds back initializations for `reason` while the spending budget can not cover an update.
+    /// A tracker already initialized is still cranked and closed, which gets its rent back.
+    pub fn set_budget_exhausted(&mut self, reason: Option<&'static str>) {
+        self.budget_exhausted = reason;
+    }
+
+    pub fn payer_pubkey(&self) -> Pubkey {
+        self.payer.pubkey()
+    }
+
+    pub fn vault_pubkey(&self) -> Pubkey {
+        self.vault.0
+    }
+
+    pub fn operator_count(&self) -> u64 {
+        self.vault.1.operator_count()
+    }
+
+    /// Records every step in `history`.
+    pub fn set_history(&mut self, history: Arc<CrankHistory>) {
+        self.history = Some(history);
+    }
+
+    /// Exports the transactions of initialize, crank and close instead of sending them.
+    pub fn set_exporter(&mut self, exporter: Arc<TransactionExporter>) {
+        self.exporter = Some(exporter);
+    }
+
+    /// Uses durable nonces of the payers for initialize, crank and close transactions.
+    pub fn set_nonce_pool(&mut self, nonces: Arc<NoncePool>) {
+        self.nonces = Some(nonces);
+    }
+
+    /// Sends up to `batch_size` cranks per v0 transaction, using an address lookup table of the
+    /// vault that is recorded in the history.
+    pub fn set_crank_batch_size(&mut self, batch_size: usize) {
+        self.crank_batch_size = batch_size.max(1);
+    }
+
+    /// Shares `compute` with other managers, so the compute units measured on one vault size the
+    /// crank batches of all of them.
+    pub fn set_compute_unit_estimator(&mut self, compute: Arc<ComputeUnitEstimator>) {
+        self.compute = compute;
+    }
+
+    /// Pays a priority fee that grows through the NCN epoch and with failed sends of the vault.
+    pub fn set_priority_fees(&mut self, priority_fees: Arc<PriorityFeeEscalator>) {
+        self.priority_fees = Some(priority_fees);
+    }
+
     pub fn set_tracker(&mut self, tracker: (Pubkey, VaultUpdateStateTracker)) {
         self.tracker = Some(tracker);
     }
@@ -74,7 +365,7 @@ impl<'a> VaultStateManager<'a> {
     }
 
     fn get_rpc_client(&self) -> RpcClient {
-        RpcClient::new_with_commitment(self.rpc_url.clone(), CommitmentConfig::confirmed())
+        RpcClient::new_with_commitment(self.rpc_url.clone(), self.commitment)
     }
 
     async fn get_update_state_tracker(
@@ -97,7 +388,7 @@ impl<'a> VaultStateManager<'a> {
         }
     }
 
-    pub async fn initialize(&self, epoch: u64) -> anyhow::Result<()> {
+    pub async fn initialize(&self, epoch: u64) -> anyhow::Result<Vec<Signature>> {
         let rpc_client = self.get_rpc_client();
 
         let tracker_pubkey = VaultUpdateStateTracker::find_program_address(
@@ -109,7 +400,7 @@ impl<'a> VaultStateManager<'a> {
 
         if self.get_update_state_tracker(&tracker_pubkey).await.is_ok() {
             log::info!("VaultUpdateStateTracker already exists: {tracker_pubkey}");
-            return Ok(());
+            return Ok(Vec::new());
         }
 
         log::info!("Initialize Vault Update State Tracker: {tracker_pubkey}");
@@ -125,31 +416,157 @@ impl<'a> VaultStateManager<'a> {
         let mut ix = ix_builder.instruction();
         ix.program_id = self.vault_program_id;
 
-        let blockhash = match rpc_client.get_latest_blockhash().await {
-            Ok(bh) => bh,
-            Err(e) => {
-                log::error!("Failed to get latest blockhash: {e}");
-                return Err(anyhow::Error::new(e).context("Failed to get latest blockhash"));
+        let sig = self
+            .send_instructions(&rpc_client, vec![ix], self.payer.as_ref(), None)
+            .await?;
+
+        if let (Some(history), Some(_sig)) = (self.history.as_ref(), sig) {
+            if let Err(e) = history.record_tracker_payer(&tracker_pubkey, &self.payer.pubkey()) {
+                log::error!("Failed to record the payer of {tracker_pubkey}: {e:?}");
             }
+        }
+
+        Ok(sig.into_iter().collect())
+    }
+
+    /// Signs `instructions` with `payer`, then sends and confirms the transaction, or exports it
+    /// if an exporter is set.
+    ///
+    /// The transaction is simulated as it is sent to set its compute-unit limit. With priority
+    /// fees, it pays the price picked for the vault, which goes up after a failed send.
+    /// With a nonce pool, the transaction advances a durable nonce of `payer` instead of using a
+    /// recent blockhash, unless all of its nonce accounts are in use. Exported transactions need a
+    /// nonce, as a recent blockhash expires before they are signed. With a lookup table, a v0
+    /// transaction is sent. Returns `None` for exported transactions and benign failures.
+    async fn send_instructions(
+        &self,
+        rpc_client: &RpcClient,
+        mut instructions: Vec<Instruction>,
+        payer: &dyn Signer,
+        lookup_table: Option<&AddressLookupTableAccount>,
+    ) -> anyhow::Result<Option<Signature>> {
+        if let Some(priority_fees) = self.priority_fees.as_ref() {
+            let slot = rpc_client.get_slot().await.context("Failed to get slot")?;
+            let price = priority_fees.compute_unit_price(&self.vault.0, slot);
+            if price > 0 {
+                log::info!(
+                    "Paying {price} micro-lamports per compute unit for vault {}",
+                    self.vault.0
+                );
+                instructions.insert(0, ComputeBudgetInstruction::set_compute_unit_price(price));
+            }
+        }
+
+        let lease = match self.nonces.as_ref() {
+            Some(nonces) => {
+                let lease = nonces.acquire(rpc_client, &payer.pubkey()).await?;
+                if lease.is_none() && self.exporter.is_some() {
+                    anyhow::bail!(
+                        "All nonce accounts of {} are in use, a recent blockhash would expire \
+                         before the exported transaction is signed",
+                        payer.pubkey()
+                    );
+                }
+                if lease.is_none() {
+                    log::debug!(
+                        "All nonce accounts of {} are in use, using a recent blockhash",
+                        payer.pubkey()
+                    );
+                }
+                lease
+            }
+            None if self.exporter.is_some() => {
+                anyhow::bail!("Exporting transactions needs a nonce pool")
+            }
+            None => None,
         };
-        let tx = Transaction::new_signed_with_payer(
-            &[ix],
-            Some(&self.payer.pubkey()),
-            &[&self.payer],
-            blockhash,
-        );
 
-        match rpc_client.send_and_confirm_transaction(&tx).await {
-            Ok(sig) => {
-                log::info!("Transaction confirmed: {sig}");
+        let blockhash = match lease.as_ref() {
+            Some(lease) => {
+                instructions.insert(0, lease.advance_instruction());
+                lease.nonce()
             }
-            Err(e) => {
-                log::error!("Failed to send transaction: {:?}", e);
-                return Err(anyhow::Error::new(e).context("Failed to send transaction"));
+            None => match rpc_client.get_latest_blockhash().await {
+                Ok(bh) => bh,
+                Err(e) => {
+                    log::error!("Failed to get latest blockhash: {e}");
+                    return Err(anyhow::Error::new(e).context("Failed to get latest blockhash"));
+                }
+            },
+        };
+
+        // Simulated last, so the limit covers the price and the nonce advance too
+        let instructions = self
+            .compute
+            .with_compute_unit_limit(rpc_client, instructions, &payer.pubkey(), lookup_table)
+            .await;
+
+        if let Some(exporter) = self.exporter.as_ref() {
+            exporter.export(&instructions, &payer.pubkey(), &[], blockhash)?;
+            if let Some(lease) = lease {
+                lease.keep();
+            }
+            return Ok(None);
+        }
+
+        let result = match lookup_table {
+            Some(lookup_table) => {
+                let message = v0::Message::try_compile(
+                    &payer.pubkey(),
+                    &instructions,
+                    &[lookup_table.clone()],
+                    blockhash,
+                )
+                .context("Failed to compile v0 message")?;
+                let tx = VersionedTransaction::try_new(VersionedMessage::V0(message), &[payer])
+                    .context("Failed to sign transaction")?;
+                rpc_client.send_and_confirm_transaction(&tx).await
+            }
+            None => {
+                let tx = sign_transaction(&instructions, payer, &[], blockhash)
+                    .context("Failed to sign transaction")?;
+                rpc_client.send_and_confirm_transaction(&tx).await
+            }
+        };
+
+        let result = check_send_result(JitoProgram::Vault, result);
+        if let Some(priority_fees) = self.priority_fees.as_ref() {
+            match result {
+                Ok(Some(_)) => priority_fees.record_success(&self.vault.0),
+                Ok(None) => {}
+                Err(_) => priority_fees.record_failure(&self.vault.0),
             }
         }
 
-        Ok(())
+        result
+    }
+
+    /// Returns the payer that initialized `tracker`, so the rent goes back to it.
+    ///
+    /// Falls back to the assigned payer when the history does not know the tracker or its payer
+    /// is no longer one of the payers.
+    fn close_payer(&self, tracker: &Pubkey) -> SharedSigner {
+        let Some(history) = self.history.as_ref() else {
+            return self.payer.clone();
+        };
+
+        match history.tracker_payer(tracker) {
+            Ok(Some(payer)) => match self.payers.get(&payer) {
+                Some(signer) => signer.clone(),
+                None => {
+                    log::warn!(
+                        "{tracker} was initialized by unknown payer {payer}, closing with {}",
+                        self.payer.pubkey()
+                    );
+                    self.payer.clone()
+                }
+            },
+            Ok(None) => self.payer.clone(),
+            Err(e) => {
+                log::error!("Failed to get the payer of {tracker}: {e:?}");
+                self.payer.clone()
+            }
+        }
     }
 
     fn sort_by_delegation_index(&self) -> Option<Vec<(Pubkey, VaultOperatorDelegation)>> {
@@ -186,115 +603,156 @@ impl<'a> VaultStateManager<'a> {
         None
     }
 
-    pub async fn crank(&self) -> anyhow::Result<()> {
+    /// Returns the delegations still to crank into the tracker, in crank order.
+    ///
+    /// The tracker's `last_updated_index` tells how far an earlier, interrupted crank got, the
+    /// delegations up to it are dropped.
+    fn delegations_to_crank(&self) -> Option<Vec<(Pubkey, VaultOperatorDelegation)>> {
+        let tracker = self.tracker?;
+        let delegations = self.sort_by_delegation_index()?;
+
+        // A tracker nothing has been cranked into yet has no last updated index
+        let last_updated_index = tracker.1.last_updated_index();
+        let cranked = match delegations
+            .iter()
+            .position(|(_pubkey, delegation)| delegation.index() == last_updated_index)
+        {
+            Some(position) => position + 1,
+            None => 0,
+        };
+
+        Some(delegations.into_iter().skip(cranked).collect())
+    }
+
+    pub async fn crank(&self) -> anyhow::Result<Vec<Signature>> {
         let rpc_client = self.get_rpc_client();
+        let mut signatures = Vec::new();
 
         // the vault does not have operator
         if self.vault.1.operator_count() == 0 {
             log::info!("The vault does not have operators currently");
-            return Ok(());
+            return Ok(signatures);
         }
 
-        let delegations = self.sort_by_delegation_index();
+        let (Some(tracker), Some(delegations), Some(remaining)) = (
+            self.tracker,
+            self.sort_by_delegation_index(),
+            self.delegations_to_crank(),
+        ) else {
+            return Ok(signatures);
+        };
+        if remaining.len() < delegations.len() {
+            log::info!(
+                "Resuming the crank of {} after {} of {} delegations",
+                tracker.0,
+                delegations.len() - remaining.len(),
+                delegations.len()
+            );
+        }
 
-        if let Some(tracker) = self.tracker {
-            if let Some(delegations) = delegations {
-                for delegation in delegations {
-                    log::info!(
-                        "Crank Vault Operator Delegation: {}, Vault Update State Tracker: {}",
-                        delegation.0,
-                        tracker.0
-                    );
+        let instructions: Vec<Instruction> = remaining
+            .iter()
+            .map(|delegation| {
+                log::info!(
+                    "Crank Vault Operator Delegation: {}, Vault Update State Tracker: {}",
+                    delegation.0,
+                    tracker.0
+                );
 
-                    let mut ix_builder = CrankVaultUpdateStateTrackerBuilder::new();
-                    ix_builder
-                        .config(self.config_pubkey)
-                        .vault(self.vault.0)
-                        .operator(delegation.1.operator)
-                        .vault_operator_delegation(delegation.0)
-                        .vault_update_state_tracker(tracker.0);
-                    let mut ix = ix_builder.instruction();
-                    ix.program_id = self.vault_program_id;
-
-                    let blockhash = match rpc_client.get_latest_blockhash().await {
-                        Ok(bh) => bh,
-                        Err(e) => {
-                            log::error!("Failed to get latest blockhash: {e}");
-                            return Err(
-                                anyhow::Error::new(e).context("Failed to get latest blockhash")
-                            );
-                        }
-                    };
-                    let tx = Transaction::new_signed_with_payer(
-                        &[ix],
-                        Some(&self.payer.pubkey()),
-                        &[&self.payer],
-                        blockhash,
-                    );
+                let mut ix_builder = CrankVaultUpdateStateTrackerBuilder::new();
+                ix_builder
+                    .config(self.config_pubkey)
+                    .vault(self.vault.0)
+                    .operator(delegation.1.operator)
+                    .vault_operator_delegation(delegation.0)
+                    .vault_update_state_tracker(tracker.0);
+                let mut ix = ix_builder.instruction();
+                ix.program_id = self.vault_program_id;
+                ix
+            })
+            .collect();
 
-                    match rpc_client.send_and_confirm_transaction(&tx).await {
-                        Ok(sig) => {
-                            log::info!("Transaction confirmed: {:?}", sig);
-                        }
-                        Err(e) => {
-                            log::error!("Failed to send transaction: {:?}", e);
-                            return Err(anyhow::Error::new(e).context("Failed to send transaction"));
-                        }
-                    }
-                }
-            }
+        // Batches go in v0 transactions through the vault's lookup table, which the history
+        // keeps track of. Exported transactions stay legacy and unbatched.
+        let lookup_table = match self.history.as_ref() {
+            Some(history) if self.crank_batch_size > 1 && self.exporter.is_none() => Some(
+                ensure_lookup_table(
+                    &rpc_client,
+                    history,
+                    &self.vault.0,
+                    tracker.1.ncn_epoch(),
+                    &self.payers,
+                    self.payer.as_ref(),
+                    &vault_addresses(&self.config_pubkey, &self.vault.0, &delegations),
+                )
+                .await?,
+            ),
+            _ => None,
+        };
+        let max_batch_size = if lookup_table.is_some() {
+            self.crank_batch_size
+        } else {
+            1
+        };
+
+        // Batches grow once a crank has been simulated, up to the cranks that fit in the largest
+        // compute-unit limit
+        let mut remaining = instructions.as_slice();
+        while let Some(ix) = remaining.first() {
+            let batch_size = self.compute.batch_size(ix, max_batch_size);
+            let (batch, rest) = remaining.split_at(batch_size.min(remaining.len()));
+            remaining = rest;
+
+            signatures.extend(
+                self.send_instructions(
+                    &rpc_client,
+                    batch.to_vec(),
+                    self.payer.as_ref(),
+                    lookup_table.as_ref(),
+                )
+                .await?,
+            );
         }
 
-        Ok(())
+        Ok(signatures)
     }
 
-    pub async fn close(&self) -> anyhow::Result<()> {
+    pub async fn close(&self) -> anyhow::Result<Vec<Signature>> {
         let rpc_client = self.get_rpc_client();
+        let mut signatures = Vec::new();
 
         if let Some(tracker) = self.tracker {
-            log::info!("Close Vault Update State Tracker: {:?}", tracker.0);
+            let payer = self.close_payer(&tracker.0);
+            log::info!(
+                "Close Vault Update State Tracker: {:?}, payer: {}",
+                tracker.0,
+                payer.pubkey()
+            );
 
             let mut ix_builder = CloseVaultUpdateStateTrackerBuilder::new();
             ix_builder
                 .config(self.config_pubkey)
                 .vault(self.vault.0)
                 .vault_update_state_tracker(tracker.0)
-                .payer(self.payer.pubkey())
+                .payer(payer.pubkey())
                 .ncn_epoch(tracker.1.ncn_epoch());
             let mut ix = ix_builder.instruction();
             ix.program_id = self.vault_program_id;
 
-            let blockhash = match rpc_client.get_latest_blockhash().await {
-                Ok(bh) => bh,
-                Err(e) => {
-                    log::error!("Failed to get latest blockhash: {e}");
-                    return Err(anyhow::Error::new(e).context("Failed to get latest blockhash"));
-                }
-            };
-            let tx = Transaction::new_signed_with_payer(
-                &[ix],
-                Some(&self.payer.pubkey()),
-                &[&self.payer],
-                blockhash,
+            signatures.extend(
+                self.send_instructions(&rpc_client, vec![ix], payer.as_ref(), None)
+                    .await?,
             );
-
-            match rpc_client.send_and_confirm_transaction(&tx).await {
-                Ok(sig) => {
-                    log::info!("Transaction confirmed: {:?}", sig);
-                }
-                Err(e) => {
-                    log::error!("Failed to send transaction: {:?}", e);
-                    return Err(anyhow::Error::new(e).context("Failed to send transaction"));
-                }
-            }
         }
 
-        Ok(())
+        Ok(signatures)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use solana_sdk::signature::Keypair;
+
     use super::*;
 
     // Operator count: 3
@@ -336,8 +794,14 @@ mod tests {
         );
         let operator_delegations = vec![delegation0, delegation1, delegation2];
 
-        let payer = Keypair::new();
-        let mut manager = VaultStateManager::new("", Pubkey::new_unique(), &payer, vault);
+        let payers = Arc::new(PayerPool::new(vec![Arc::new(Keypair::new())], &[]).unwrap());
+        let mut manager = VaultStateManager::new(
+            "",
+            CommitmentConfig::confirmed(),
+            Pubkey::new_unique(),
+            payers.clone(),
+            vault,
+        );
         manager.tracker = Some((
             Pubkey::new_unique(),
             VaultUpdateStateTracker::new(Pubkey::new_unique(), 0, 0),
@@ -392,8 +856,14 @@ mod tests {
         );
         let operator_delegations = vec![delegation0, delegation1, delegation2];
 
-        let payer = Keypair::new();
-        let mut manager = VaultStateManager::new("", Pubkey::new_unique(), &payer, vault);
+        let payers = Arc::new(PayerPool::new(vec![Arc::new(Keypair::new())], &[]).unwrap());
+        let mut manager = VaultStateManager::new(
+            "",
+            CommitmentConfig::confirmed(),
+            Pubkey::new_unique(),
+            payers.clone(),
+            vault,
+        );
         manager.tracker = Some((
             Pubkey::new_unique(),
             VaultUpdateStateTracker::new(Pubkey::new_unique(), 1, 0),
@@ -448,8 +918,14 @@ mod tests {
         );
         let operator_delegations = vec![delegation0, delegation1, delegation2];
 
-        let payer = Keypair::new();
-        let mut manager = VaultStateManager::new("", Pubkey::new_unique(), &payer, vault);
+        let payers = Arc::new(PayerPool::new(vec![Arc::new(Keypair::new())], &[]).unwrap());
+        let mut manager = VaultStateManager::new(
+            "",
+            CommitmentConfig::confirmed(),
+            Pubkey::new_unique(),
+            payers.clone(),
+            vault,
+        );
         manager.tracker = Some((
             Pubkey::new_unique(),
             VaultUpdateStateTracker::new(Pubkey::new_unique(), 2, 0),
@@ -464,4 +940,204 @@ mod tests {
             vec![delegation2, delegation0, delegation1]
         );
     }
+
+    #[test]
+    fn test_delegations_to_crank() {
+        let mut vault = Vault::new(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            0,
+            Pubkey::new_unique(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        )
+        .unwrap();
+        vault.increment_operator_count().unwrap();
+        vault.increment_operator_count().unwrap();
+        vault.increment_operator_count().unwrap();
+
+        let delegation0 = (
+            Pubkey::new_unique(),
+            VaultOperatorDelegation::new(Pubkey::default(), Pubkey::default(), 0, 0, 0),
+        );
+        let delegation1 = (
+            Pubkey::new_unique(),
+            VaultOperatorDelegation::new(Pubkey::default(), Pubkey::default(), 1, 0, 0),
+        );
+        let delegation2 = (
+            Pubkey::new_unique(),
+            VaultOperatorDelegation::new(Pubkey::default(), Pubkey::default(), 2, 0, 0),
+        );
+
+        let payers = Arc::new(PayerPool::new(vec![Arc::new(Keypair::new())], &[]).unwrap());
+        let mut manager = VaultStateManager::new(
+            "",
+            CommitmentConfig::confirmed(),
+            Pubkey::new_unique(),
+            payers.clone(),
+            (Pubkey::new_unique(), vault),
+        );
+        manager.operator_delegations = Some(vec![delegation0, delegation1, delegation2]);
+
+        // NCN epoch 1 cranks 1, 2, 0
+        let mut tracker = VaultUpdateStateTracker::new(Pubkey::new_unique(), 1, 0);
+        manager.tracker = Some((Pubkey::new_unique(), tracker));
+        assert_eq!(
+            manager.delegations_to_crank().unwrap(),
+            vec![delegation1, delegation2, delegation0]
+        );
+
+        // Half cranked before an interruption
+        tracker.check_and_update_index(1, 3).unwrap();
+        manager.tracker = Some((Pubkey::new_unique(), tracker));
+        assert_eq!(
+            manager.delegations_to_crank().unwrap(),
+            vec![delegation2, delegation0]
+        );
+
+        tracker.check_and_update_index(2, 3).unwrap();
+        tracker.check_and_update_index(0, 3).unwrap();
+        manager.tracker = Some((Pubkey::new_unique(), tracker));
+        assert!(manager.delegations_to_crank().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_is_cranked() {
+        let mut vault = Vault::new(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            0,
+            Pubkey::new_unique(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        )
+        .unwrap();
+        let payers = Arc::new(PayerPool::new(vec![Arc::new(Keypair::new())], &[]).unwrap());
+
+        // No operators: nothing to crank
+        let mut manager = VaultStateManager::new(
+            "",
+            CommitmentConfig::confirmed(),
+            Pubkey::new_unique(),
+            payers.clone(),
+            (Pubkey::new_unique(), vault),
+        );
+        assert!(!manager.is_cranked());
+        manager.tracker = Some((
+            Pubkey::new_unique(),
+            VaultUpdateStateTracker::new(Pubkey::new_unique(), 1, 0),
+        ));
+        assert!(manager.is_cranked());
+
+        vault.increment_operator_count().unwrap();
+        vault.increment_operator_count().unwrap();
+        vault.increment_operator_count().unwrap();
+
+        // Freshly initialized tracker
+        let mut manager = VaultStateManager::new(
+            "",
+            CommitmentConfig::confirmed(),
+            Pubkey::new_unique(),
+            payers.clone(),
+            (Pubkey::new_unique(), vault),
+        );
+        let mut tracker = VaultUpdateStateTracker::new(Pubkey::new_unique(), 1, 0);
+        manager.tracker = Some((Pubkey::new_unique(), tracker));
+        assert!(!manager.is_cranked());
+
+        // NCN epoch 1 cranks 1, 2, 0
+        tracker.check_and_update_index(1, 3).unwrap();
+        tracker.check_and_update_index(2, 3).unwrap();
+        manager.tracker = Some((Pubkey::new_unique(), tracker));
+        assert!(!manager.is_cranked());
+
+        tracker.check_and_update_index(0, 3).unwrap();
+        manager.tracker = Some((Pubkey::new_unique(), tracker));
+        assert!(manager.is_cranked());
+    }
+
+    #[test]
+    fn test_next_action() {
+        let epoch_length = 100;
+        let mut vault = Vault::new(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            0,
+            Pubkey::new_unique(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        )
+        .unwrap();
+        vault.increment_operator_count().unwrap();
+        let vault_pubkey = Pubkey::new_unique();
+        let delegation = (
+            Pubkey::new_unique(),
+            VaultOperatorDelegation::new(vault_pubkey, Pubkey::default(), 0, 0, 0),
+        );
+        let payers = Arc::new(PayerPool::new(vec![Arc::new(Keypair::new())], &[]).unwrap());
+
+        let mut manager = VaultStateManager::new(
+            "",
+            CommitmentConfig::confirmed(),
+            Pubkey::new_unique(),
+            payers.clone(),
+            (vault_pubkey, vault),
+        );
+
+        // Last updated in epoch 0
+        assert_eq!(manager.next_action(0, epoch_length), VaultAction::UpToDate);
+        assert_eq!(
+            manager.next_action(2, epoch_length),
+            VaultAction::Initialize
+        );
+        assert_eq!(manager.missed_epoch(1, epoch_length), None);
+        assert_eq!(manager.missed_epoch(2, epoch_length), Some(1));
+
+        let mut tracker = VaultUpdateStateTracker::new(vault_pubkey, 2, 0);
+        manager.tracker = Some((Pubkey::new_unique(), tracker));
+        assert_eq!(
+            manager.next_action(2, epoch_length),
+            VaultAction::Skip("delegations do not match the operator count")
+        );
+
+        manager.set_operator_delegations(&[delegation]);
+        assert_eq!(manager.next_action(2, epoch_length), VaultAction::Crank);
+
+        // A tracker of an earlier epoch is never cranked
+        assert_eq!(
+            manager.next_action(3, epoch_length),
+            VaultAction::CloseStale
+        );
+        assert!(matches!(
+            manager.next_action(1, epoch_length),
+            VaultAction::Skip(_)
+        ));
+
+        // A spent budget holds back initializations, not the crank and close of a tracker
+        manager.set_budget_exhausted(Some("the epoch budget can not cover another update"));
+        assert_eq!(manager.next_action(2, epoch_length), VaultAction::Crank);
+        tracker.check_and_update_index(0, 1).unwrap();
+        manager.tracker = Some((Pubkey::new_unique(), tracker));
+        assert_eq!(manager.next_action(2, epoch_length), VaultAction::Close);
+        manager.tracker = None;
+        assert_eq!(
+            manager.next_action(2, epoch_length),
+            VaultAction::Skip("the epoch budget can not cover another update")
+        );
+    }
 }
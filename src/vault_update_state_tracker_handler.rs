@@ -12,30 +12,38 @@ use jito_vault_core::{
     vault_operator_delegation::VaultOperatorDelegation,
     vault_update_state_tracker::VaultUpdateStateTracker,
 };
-use solana_account_decoder::UiAccountEncoding;
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
 use solana_client::{
     nonblocking::rpc_client::RpcClient,
     rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
-    rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
 };
-use solana_sdk::{
-    commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Keypair, signer::Signer,
-    system_program, transaction::Transaction,
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, system_program};
+
+use crate::{
+    account_layout::{
+        discriminator_filter, pubkey_filter, NCN_OPERATOR_STATE_NCN_OFFSET,
+        NCN_OPERATOR_STATE_OPERATOR_OFFSET, NCN_VAULT_TICKET_NCN_OFFSET,
+        NCN_VAULT_TICKET_VAULT_OFFSET,
+    },
+    program_error::{check_send_result, JitoProgram},
+    signer::{sign_transaction, SharedSigner},
 };
 
-pub struct VaultUpdateStateTrackerHandler<'a> {
+pub struct VaultUpdateStateTrackerHandler {
     rpc_url: String,
-    payer: &'a Keypair,
+    commitment: CommitmentConfig,
+    payer: SharedSigner,
     restaking_program_id: Pubkey,
     vault_program_id: Pubkey,
     config_address: Pubkey,
     epoch_length: u64,
 }
 
-impl<'a> VaultUpdateStateTrackerHandler<'a> {
+impl VaultUpdateStateTrackerHandler {
     pub fn new(
         rpc_url: &str,
-        payer: &'a Keypair,
+        commitment: CommitmentConfig,
+        payer: SharedSigner,
         restaking_program_id: Pubkey,
         vault_program_id: Pubkey,
         config_address: Pubkey,
@@ -43,6 +51,7 @@ impl<'a> VaultUpdateStateTrackerHandler<'a> {
     ) -> Self {
         Self {
             rpc_url: rpc_url.to_string(),
+            commitment,
             payer,
             restaking_program_id,
             vault_program_id,
@@ -52,7 +61,7 @@ impl<'a> VaultUpdateStateTrackerHandler<'a> {
     }
 
     fn get_rpc_client(&self) -> RpcClient {
-        RpcClient::new_with_commitment(self.rpc_url.clone(), CommitmentConfig::confirmed())
+        RpcClient::new_with_commitment(self.rpc_url.clone(), self.commitment)
     }
 
     async fn get_update_state_tracker(
@@ -95,19 +104,25 @@ impl<'a> VaultUpdateStateTrackerHandler<'a> {
         }
     }
 
+    /// Returns the vaults that have an `NcnVaultTicket` with `ncn_address`.
+    ///
+    /// Tickets are filtered by NCN on the RPC side and only their `vault` field is downloaded.
     pub async fn get_ncn_vault_tickets(&self, ncn_address: Pubkey) -> anyhow::Result<Vec<Pubkey>> {
         let rpc_client = self.get_rpc_client();
         let accounts = rpc_client
             .get_program_accounts_with_config(
                 &self.restaking_program_id,
                 RpcProgramAccountsConfig {
-                    filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new(
-                        0,
-                        MemcmpEncodedBytes::Bytes(vec![NcnVaultTicket::DISCRIMINATOR]),
-                    ))]),
+                    filters: Some(vec![
+                        discriminator_filter(NcnVaultTicket::DISCRIMINATOR),
+                        pubkey_filter(NCN_VAULT_TICKET_NCN_OFFSET, &ncn_address),
+                    ]),
                     account_config: RpcAccountInfoConfig {
                         encoding: Some(UiAccountEncoding::Base64),
-                        data_slice: None,
+                        data_slice: Some(UiDataSliceConfig {
+                            offset: NCN_VAULT_TICKET_VAULT_OFFSET,
+                            length: 32,
+                        }),
                         commitment: None,
                         min_context_slot: None,
                     },
@@ -120,46 +135,41 @@ impl<'a> VaultUpdateStateTrackerHandler<'a> {
                 format!("Failed to get NcnVaultTicket accounts: {}", ncn_address)
             })?;
 
-        let tickets: Vec<NcnVaultTicket> = accounts
+        let vaults: Vec<Pubkey> = accounts
             .iter()
-            .filter_map(|(_ncn_pubkey, ncn_vault_ticket)| {
-                match NcnVaultTicket::try_from_slice_unchecked(&ncn_vault_ticket.data) {
-                    Ok(ticket) => Some(*ticket),
+            .filter_map(
+                |(ticket_pubkey, ticket)| match Pubkey::try_from(ticket.data.as_slice()) {
+                    Ok(vault) => Some(vault),
                     Err(e) => {
-                        log::error!("Error deserializing NcnVaultTicket: {:?}", e);
+                        log::error!("Error reading vault of NcnVaultTicket {ticket_pubkey}: {e:?}");
                         None
                     }
-                }
-            })
-            .collect();
-
-        let vaults: Vec<Pubkey> = tickets
-            .into_iter()
-            .filter_map(|ticket| {
-                if ticket.ncn == ncn_address {
-                    Some(ticket.vault)
-                } else {
-                    None
-                }
-            })
+                },
+            )
             .collect();
 
         Ok(vaults)
     }
 
+    /// Returns the operators that have an `NcnOperatorState` with `ncn_address`.
+    ///
+    /// States are filtered by NCN on the RPC side and only their `operator` field is downloaded.
     pub async fn get_operators(&self, ncn_address: Pubkey) -> anyhow::Result<Vec<Pubkey>> {
         let rpc_client = self.get_rpc_client();
         let accounts = rpc_client
             .get_program_accounts_with_config(
                 &self.restaking_program_id,
                 RpcProgramAccountsConfig {
-                    filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new(
-                        0,
-                        MemcmpEncodedBytes::Bytes(vec![NcnOperatorState::DISCRIMINATOR]),
-                    ))]),
+                    filters: Some(vec![
+                        discriminator_filter(NcnOperatorState::DISCRIMINATOR),
+                        pubkey_filter(NCN_OPERATOR_STATE_NCN_OFFSET, &ncn_address),
+                    ]),
                     account_config: RpcAccountInfoConfig {
                         encoding: Some(UiAccountEncoding::Base64),
-                        data_slice: None,
+                        data_slice: Some(UiDataSliceConfig {
+                            offset: NCN_OPERATOR_STATE_OPERATOR_OFFSET,
+                            length: 32,
+                        }),
                         commitment: None,
                         min_context_slot: None,
                     },
@@ -172,28 +182,19 @@ impl<'a> VaultUpdateStateTrackerHandler<'a> {
                 format!("Failed to get NcnOperatorState accounts: {}", ncn_address)
             })?;
 
-        let states: Vec<NcnOperatorState> = accounts
+        let operators: Vec<Pubkey> = accounts
             .iter()
-            .filter_map(|(_ncn_pubkey, ncn_vault_ticket)| {
-                match NcnOperatorState::try_from_slice_unchecked(&ncn_vault_ticket.data) {
-                    Ok(state) => Some(*state),
+            .filter_map(
+                |(state_pubkey, state)| match Pubkey::try_from(state.data.as_slice()) {
+                    Ok(operator) => Some(operator),
                     Err(e) => {
-                        log::error!("Error deserializing NcnOperatorState: {:?}", e);
+                        log::error!(
+                            "Error reading operator of NcnOperatorState {state_pubkey}: {e:?}"
+                        );
                         None
                     }
-                }
-            })
-            .collect();
-
-        let operators: Vec<Pubkey> = states
-            .into_iter()
-            .filter_map(|state| {
-                if state.ncn == ncn_address {
-                    Some(state.operator)
-                } else {
-                    None
-                }
-            })
+                },
+            )
             .collect();
 
         Ok(operators)
@@ -232,30 +233,36 @@ impl<'a> VaultUpdateStateTrackerHandler<'a> {
                     return Err(anyhow::Error::new(e).context("Failed to get latest blockhash"));
                 }
             };
-            let tx = Transaction::new_signed_with_payer(
-                &[ix],
-                Some(&self.payer.pubkey()),
-                &[&self.payer],
-                blockhash,
-            );
+            let tx = sign_transaction(&[ix], self.payer.as_ref(), &[], blockhash)
+                .context("Failed to sign transaction")?;
 
-            match rpc_client.send_and_confirm_transaction(&tx).await {
-                Ok(sig) => {
-                    log::info!("Transaction confirmed: {:?}", sig);
-                }
-                Err(e) => {
-                    log::error!("Failed to send transaction: {:?}", e);
-                    return Err(anyhow::Error::new(e).context("Failed to send transaction"));
-                }
-            }
+            check_send_result(
+                JitoProgram::Vault,
+                rpc_client.send_and_confirm_transaction(&tx).await,
+            )?;
         }
 
         Ok(())
     }
 
+    /// Cranks the delegations of `operators` into the tracker of the current epoch.
+    ///
+    /// Fails before sending anything if that tracker has not been initialized.
     pub async fn crank(&self, vault: &Pubkey, operators: &[Pubkey]) -> anyhow::Result<()> {
         let rpc_client = self.get_rpc_client();
         let slot = rpc_client.get_slot().await.expect("get slot");
+        let ncn_epoch = slot / self.epoch_length;
+
+        let tracker =
+            VaultUpdateStateTracker::find_program_address(&self.vault_program_id, vault, ncn_epoch)
+                .0;
+        self.get_update_state_tracker(&tracker)
+            .await
+            .with_context(|| {
+                format!(
+                    "VaultUpdateStateTracker of epoch {ncn_epoch} is not initialized: {tracker}"
+                )
+            })?;
 
         for operator in operators {
             let vault_operator_delegation = VaultOperatorDelegation::find_program_address(
@@ -274,12 +281,6 @@ impl<'a> VaultUpdateStateTrackerHandler<'a> {
                 );
                 continue;
             }
-            let tracker = VaultUpdateStateTracker::find_program_address(
-                &self.vault_program_id,
-                vault,
-                slot / self.epoch_length,
-            )
-            .0;
 
             log::info!(
                 "Crank Vault Operator Delegation: {}, Vault Update State Tracker: {}",
@@ -304,22 +305,13 @@ impl<'a> VaultUpdateStateTrackerHandler<'a> {
                     return Err(anyhow::Error::new(e).context("Failed to get latest blockhash"));
                 }
             };
-            let tx = Transaction::new_signed_with_payer(
-                &[ix],
-                Some(&self.payer.pubkey()),
-                &[&self.payer],
-                blockhash,
-            );
+            let tx = sign_transaction(&[ix], self.payer.as_ref(), &[], blockhash)
+                .context("Failed to sign transaction")?;
 
-            match rpc_client.send_and_confirm_transaction(&tx).await {
-                Ok(sig) => {
-                    log::info!("Transaction confirmed: {:?}", sig);
-                }
-                Err(e) => {
-                    log::error!("Failed to send transaction: {:?}", e);
-                    return Err(anyhow::Error::new(e).context("Failed to send transaction"));
-                }
-            }
+            check_send_result(
+                JitoProgram::Vault,
+                rpc_client.send_and_confirm_transaction(&tx).await,
+            )?;
         }
 
         Ok(())
@@ -327,7 +319,6 @@ impl<'a> VaultUpdateStateTrackerHandler<'a> {
 
     pub async fn close(&self, vaults: &[Pubkey], epoch: u64) -> anyhow::Result<()> {
         let rpc_client = self.get_rpc_client();
-        let slot = rpc_client.get_slot().await.expect("get slot");
 
         for vault in vaults {
             let mut ix_builder = CloseVaultUpdateStateTrackerBuilder::new();
@@ -342,7 +333,7 @@ impl<'a> VaultUpdateStateTrackerHandler<'a> {
                 .vault(*vault)
                 .vault_update_state_tracker(tracker)
                 .payer(self.payer.pubkey())
-                .ncn_epoch(slot / self.epoch_length);
+                .ncn_epoch(epoch);
             let mut ix = ix_builder.instruction();
             ix.program_id = self.vault_program_id;
 
@@ -353,22 +344,13 @@ impl<'a> VaultUpdateStateTrackerHandler<'a> {
                     return Err(anyhow::Error::new(e).context("Failed to get latest blockhash"));
                 }
             };
-            let tx = Transaction::new_signed_with_payer(
-                &[ix],
-                Some(&self.payer.pubkey()),
-                &[&self.payer],
-                blockhash,
-            );
+            let tx = sign_transaction(&[ix], self.payer.as_ref(), &[], blockhash)
+                .context("Failed to sign transaction")?;
 
-            match rpc_client.send_and_confirm_transaction(&tx).await {
-                Ok(sig) => {
-                    log::info!("Transaction confirmed: {:?}", sig);
-                }
-                Err(e) => {
-                    log::error!("Failed to send transaction: {:?}", e);
-                    return Err(anyhow::Error::new(e).context("Failed to send transaction"));
-                }
-            }
+            check_send_result(
+                JitoProgram::Vault,
+                rpc_client.send_and_confirm_transaction(&tx).await,
+            )?;
         }
 
         Ok(())